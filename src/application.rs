@@ -4,35 +4,74 @@ use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use std::collections::btree_map;
 use std::fmt::{Display, Formatter};
-use std::{collections::BTreeMap, path::PathBuf};
+use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
 
 use anyhow::{bail, ensure, Context, Result};
 
-use dialoguer::FuzzySelect;
+use crate::clock::Clock;
+use crate::listing::{Listing, Row};
+use crate::time::{Calendar, FlatTime};
 
-/// User exposed (via [``SubCommand::config``](crate::cli::SubCommand::Config)) configuration.
+/// User exposed (via `prac config`) configuration.
 #[serde_as]
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct UserConfig {
     #[serde_as(as = "serde_with::DurationSeconds<i64>")]
     #[serde(rename = "grace_period_in_seconds")]
     /// Grace period adds extra time in progress display. This aids against practices creeping earlier.
     pub grace_period: Duration,
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    #[serde(
+        rename = "session_cap_in_seconds",
+        default = "UserConfig::default_session_cap"
+    )]
+    /// Most time a session without a max time will log, in case it's left running.
+    pub session_cap: Duration,
+    /// How to be notified when a session's time is up.
+    #[serde(default)]
+    pub notifications: crate::notify::Notifications,
+    /// Colors of the bars of `prac list`.
+    #[serde(default)]
+    pub theme: crate::theme::Theme,
+    /// How the danger bar of `prac list` sums up progress.
+    #[serde(default)]
+    pub danger: crate::listing::DangerStrategy,
+    /// How long months and years are in time spans.
+    #[serde(default)]
+    pub calendar: Calendar,
+}
+
+impl UserConfig {
+    const fn default_session_cap() -> Duration {
+        Duration::hours(4)
+    }
 }
 
 impl Default for UserConfig {
     fn default() -> Self {
         Self {
             grace_period: Duration::zero(),
+            session_cap: Self::default_session_cap(),
+            notifications: crate::notify::Notifications::default(),
+            theme: crate::theme::Theme::default(),
+            danger: crate::listing::DangerStrategy::default(),
+            calendar: Calendar::default(),
         }
     }
 }
 /// This is the application config, which includes user-editable [``UserConfig``]
 /// , as well as other items like version that the user probably shouldn't touch.
 #[derive(Serialize, Deserialize)]
+#[allow(clippy::struct_field_names)]
 struct Config {
     /// Version of prac that created this state file.
     version: String,
+    /// Layout of this state file, see [``crate::migration``].
+    schema: usize,
     /// User editable configuration.
     user_config: UserConfig,
 }
@@ -41,19 +80,77 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             version: env!("CARGO_PKG_VERSION").to_owned(),
+            schema: crate::migration::SCHEMA_VERSION,
             user_config: UserConfig::default(),
         }
     }
 }
 
+/// A single completed participation in a practice, as recorded by
+/// [``StateTransition::Log``].
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Completion {
+    /// When participation began
+    start: DateTime<Utc>,
+    /// When participation ended
+    end: DateTime<Utc>,
+    /// Time spent participating
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    duration: Duration,
+    /// Optional note on how it went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// Breaks taken partway through
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pauses: Vec<Pause>,
+}
+
+impl Completion {
+    pub const fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    pub const fn end(&self) -> DateTime<Utc> {
+        self.end
+    }
+
+    /// Time spent participating, not counting pauses
+    pub const fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn pauses(&self) -> &[Pause] {
+        &self.pauses
+    }
+}
+
+/// A break in participation, not counted toward the time of a [``Completion``].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pause {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Pause {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
 /// A practice is an activity that you wish to repeat every so often. Not so much a task (completion oriented), not a habit (in absolute time), or scheduling-item.
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct Practice {
     /// Time practice created by user
     created: DateTime<Utc>,
-    /// Last time practice was logged
-    logged: DateTime<Utc>,
+    /// Last time practice was reset without participation (see [``StateTransition::Reset``]).
+    /// Statefiles from before completions were recorded store their last log here.
+    reset: DateTime<Utc>,
     /// How often you wish to repeat practice (starting from last log)
     #[serde_as(as = "serde_with::DurationSeconds<i64>")]
     period: Duration,
@@ -61,33 +158,99 @@ pub struct Practice {
     name: String,
     /// Plain-text notes where user can set goals, track progress, etc.
     notes: String,
-    /// Cumulative time spent on this practice
+    /// Time logged before completions were recorded individually
     #[serde_as(as = "serde_with::DurationSeconds<i64>")]
-    cumulative: Duration,
-    // TODO maybe a Completion struct? then a body enum {practice, Task} that contains Vec<Comepletion> for practice and raw
-    // Completion for task. Trying not to prematurely optimize.
+    untracked: Duration,
+    /// Every participation in this practice, ordered by end time
+    completions: Vec<Completion>,
+    /// Groups this practice belongs to, like "health" or "study"
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    /// Importance relative to other practices, for the weighted danger bar
+    #[serde(default = "Practice::default_weight")]
+    weight: f64,
 }
 
 impl Practice {
     /// Create a new practice with the given name, notes, and period, starting with no logged time.
-    fn new(name: String, notes: String, period: Duration) -> Self {
-        let created = Utc::now();
-        let logged = created;
-
+    const fn new(name: String, notes: String, period: Duration, created: DateTime<Utc>) -> Self {
         Self {
             created,
-            logged,
+            reset: created,
             period,
             name,
             notes,
-            cumulative: Duration::seconds(0),
+            untracked: Duration::zero(),
+            completions: Vec::new(),
+            tags: BTreeSet::new(),
+            weight: Self::default_weight(),
         }
     }
 
-    /// Number of seconds elapsed since last practice
-    fn elapsed(&self) -> Duration {
-        let now = Utc::now();
-        now - self.logged
+    const fn default_weight() -> f64 {
+        1.0
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+
+    /// How often you wish to repeat practice (starting from last log)
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub const fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub const fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub const fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Every participation in this practice, ordered by end time
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    /// Last time practice was logged (or reset)
+    pub fn logged(&self) -> DateTime<Utc> {
+        self.completions
+            .last()
+            .map_or(self.reset, |completion| completion.end.max(self.reset))
+    }
+
+    /// Cumulative time spent on this practice
+    pub fn cumulative(&self) -> Duration {
+        self.completions
+            .iter()
+            .fold(self.untracked, |sum, completion| {
+                crate::time::saturating_add(sum, completion.duration)
+            })
+    }
+
+    /// Progress through this practice's period, with `grace_period` added on.
+    pub fn period_progress(&self, grace_period: Duration) -> Period {
+        Period {
+            since: self.logged(),
+            length: crate::time::saturating_add(self.period, grace_period),
+        }
+    }
+
+    /// Add a completion, keeping completions ordered by end time.
+    fn record(&mut self, completion: Completion) {
+        let index = self
+            .completions
+            .partition_point(|existing| existing.end <= completion.end);
+        self.completions.insert(index, completion);
     }
 }
 
@@ -101,11 +264,57 @@ impl Display for Practice {
     }
 }
 
+/// A session running in the background, started by [``StateTransition::StartSession``] and
+/// finished by logging its practice.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ActiveSession {
+    /// Name of practice in session
+    pub name: String,
+    /// When the session began
+    pub start: DateTime<Utc>,
+    /// Most time the session will log, in case it is forgotten
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub max_time: Duration,
+}
+
+impl ActiveSession {
+    /// Time to be logged if the session were stopped at `now`.
+    pub fn elapsed(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.start).min(self.max_time)
+    }
+}
+
+/// Progress through a practice's period, as drawn by `prac list`.
+#[derive(Clone, Copy)]
+pub struct Period {
+    /// When the practice was last logged (or reset).
+    pub since: DateTime<Utc>,
+    /// Period, including any grace period.
+    pub length: Duration,
+}
+
+impl Period {
+    /// Fraction of the period elapsed by `now`, over 1 once overdue.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self, now: DateTime<Utc>) -> f64 {
+        (now - self.since).num_seconds() as f64 / self.length.num_seconds() as f64
+    }
+}
+
 /// Application state containing all data for prac, also w/ serialization/deserialization.
 #[derive(Serialize, Deserialize, Default)]
 pub struct State {
     config: Config,
     practices: BTreeMap<String, Practice>,
+    /// Sequence number of the last [``Journal``](crate::journal::Journal) entry applied to this state.
+    #[serde(default)]
+    journal_seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_session: Option<ActiveSession>,
+    /// What to tell the time by, the system's if not set.
+    #[serde(skip)]
+    clock: Option<Arc<dyn Clock>>,
 }
 
 impl State {
@@ -113,123 +322,63 @@ impl State {
         Self::default()
     }
 
-    pub fn update_version(&mut self) {
-        self.config.version = env!("CARGO_PKG_VERSION").to_owned();
+    /// The current time, by this state's clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock
+            .as_ref()
+            .map_or_else(Utc::now, |clock| clock.now())
     }
 
-    pub fn list(&self, cumulative: bool, period: bool, danger: bool) -> Result<()> {
-        if self.practices.is_empty() {
-            println!("You don't have any practices yet. Add some with `prac add`.");
-            return Ok(());
-        }
+    /// This state's clock, see [``State::set_clock``].
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock
+            .clone()
+            .unwrap_or_else(|| Arc::new(crate::clock::SystemClock))
+    }
 
-        let start_messages = self
-            .practices
-            .keys()
-            .map(|name| format!("  {name} "))
-            .collect::<Vec<_>>();
+    /// Tell the time by `clock` rather than the system's.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = Some(clock);
+    }
 
-        let end_messages = &self
+    pub fn update_version(&mut self) {
+        env!("CARGO_PKG_VERSION").clone_into(&mut self.config.version);
+    }
+
+    /// Every practice's progress through its period as of `now`, for `prac list`.
+    pub fn listing(&self, now: DateTime<Utc>) -> Listing {
+        let grace_period = self.config.user_config.grace_period;
+        let practices = self
             .practices
             .values()
             .map(|practice| {
-                let period_time = super::time::FlatTime::from(practice.period);
-                let cumulative_time = super::time::FlatTime::from(practice.cumulative);
-
-                match (cumulative, period) {
-                    (true, true) => format!(
-                        " {} c / {} p  ",
-                        cumulative_time.format_abbreviated(),
-                        period_time.format_abbreviated(),
-                    ),
-                    (true, false) => {
-                        format!(" {}  ", cumulative_time.format())
-                    }
-                    (false, true) => format!(" {}  ", period_time.format()),
-                    (false, false) => "  ".to_string(),
+                let progress = practice.period_progress(grace_period);
+                Row {
+                    name: practice.name.clone(),
+                    period: practice.period,
+                    elapsed: now - progress.since,
+                    fraction: progress.fraction(now),
+                    cumulative: practice.cumulative(),
+                    created: practice.created,
+                    logged: progress.since,
+                    tags: practice.tags.iter().cloned().collect(),
+                    weight: practice.weight,
                 }
             })
             .collect::<Vec<_>>();
 
-        let max_start_len = start_messages.iter().map(String::len).max().unwrap();
-        let max_end_len = end_messages.iter().map(String::len).max().unwrap();
-
-        let padded_start_messages = start_messages
-            .iter()
-            .map(|s| format!("{s:>max_start_len$}"))
-            .collect::<Vec<_>>();
-        let padded_end_messages = end_messages
-            .iter()
-            .map(|s| format!("{s:<max_end_len$}"))
-            .collect::<Vec<_>>();
-
-        let term_width = termion::terminal_size()
-            .context("failed to obtain termsize")?
-            .0;
-        let padding_width = max_start_len + max_end_len;
-        let bar_width = (term_width as usize)
-            .checked_sub(padding_width)
-            .with_context(|| {
-                format!("term width {term_width} too small, must be at least {padding_width}")
-            })?;
-
-        println!();
-        for (practice, start, end) in itertools::izip!(
-            self.practices.values(),
-            padded_start_messages,
-            padded_end_messages
-        ) {
-            let grace_adjusted_period = practice.period + self.config.user_config.grace_period;
-            #[allow(clippy::cast_precision_loss)]
-            let fraction = practice.elapsed().num_seconds() as f64
-                / grace_adjusted_period.num_seconds() as f64;
-
-            let whole_bar = format!("{}{}{}", start, crate::utils::bar(bar_width, fraction), end);
-
-            println!("{whole_bar}");
-        }
-        println!();
-
-        if danger {
-            let sum_progress: i64 = self
-                .practices
-                .values()
-                .map(|p| p.elapsed().num_seconds())
-                .sum();
-            let sum_period: i64 = self
-                .practices
-                .values()
-                .map(|p| (p.period + self.config.user_config.grace_period).num_seconds())
-                .sum();
-
-            #[allow(clippy::cast_precision_loss)]
-            let sum_fraction = sum_progress as f64 / sum_period as f64;
-
-            // TODO make red
-            let sum_bar = crate::utils::bar(bar_width, sum_fraction);
-            let start = format!("  {} ", "danger");
-            let end = String::new();
-
-            println!("{start:>max_start_len$}{sum_bar}{end:<max_end_len$}");
-        }
-
-        Ok(())
+        let config = &self.config.user_config;
+        Listing::new(practices, grace_period, config.danger, config.calendar)
     }
 
-    /// Find the name of a practice either validating an name input, or if not provided, prompting the user to select one.
-    pub fn find_name(&self) -> Result<&str> {
-        let options = &self.practices.keys().collect::<Vec<_>>();
-
-        let selection_index = FuzzySelect::new()
-            .with_prompt("Select practice")
-            .items(options)
-            .interact_opt()
-            .context("Selection error.")?;
+    /// Every practice, ordered by name.
+    pub fn practices(&self) -> impl Iterator<Item = &Practice> {
+        self.practices.values()
+    }
 
-        match selection_index {
-            Some(i) => Ok(options[i].as_str()),
-            None => bail!("No item selected"),
-        }
+    /// The named practice, if there is one.
+    pub fn practice(&self, name: &str) -> Option<&Practice> {
+        self.practices.get(name)
     }
 
     pub fn get_notes(&self, name: &str) -> Result<&str> {
@@ -242,10 +391,50 @@ impl State {
         Ok(notes)
     }
 
-    pub fn get_user_config(&self) -> &UserConfig {
+    /// Progress through the named practice's period.
+    pub fn period(&self, name: &str) -> Result<Period> {
+        let practice = self
+            .practices
+            .get(name)
+            .with_context(|| format!("\"{name}\" not found."))?;
+        Ok(practice.period_progress(self.config.user_config.grace_period))
+    }
+
+    pub fn get_tags(&self, name: &str) -> Result<Vec<String>> {
+        let practice = self
+            .practices
+            .get(name)
+            .with_context(|| format!("\"{name}\" not found."))?;
+        Ok(practice.tags.iter().cloned().collect())
+    }
+
+    pub const fn get_user_config(&self) -> &UserConfig {
         &self.config.user_config
     }
 
+    pub const fn active_session(&self) -> Option<&ActiveSession> {
+        self.active_session.as_ref()
+    }
+
+    /// Clear the active session if it's of the named practice.
+    fn end_session_of(&mut self, name: &str) {
+        if self
+            .active_session
+            .as_ref()
+            .is_some_and(|session| session.name == name)
+        {
+            self.active_session = None;
+        }
+    }
+
+    pub const fn journal_seq(&self) -> u64 {
+        self.journal_seq
+    }
+
+    pub(crate) const fn set_journal_seq(&mut self, seq: u64) {
+        self.journal_seq = seq;
+    }
+
     /// Get the path to the default location state file.
     /// Search order: [`dirs::data_dir`]/prac/prac.json, [`dirs::home_dir`]/.prac.json
     /// This may be overridden elsewhere, in either the `PRAC_PATH` env var, or with the --file arg.
@@ -265,12 +454,14 @@ impl State {
     }
 }
 
-/// See [``Cli::SubCommand``](crate::cli::SubCommand) for documentation.
+/// A change to [``State``], applied through the [``Journal``](crate::journal::Journal) with
+/// [``Journal::apply``](crate::journal::Journal::apply). See `prac help` for what each does.
 #[warn(clippy::print_stdout, clippy::print_stderr)]
 #[allow(clippy::missing_docs_in_private_items)]
 // TODO move option hanlding, completely abstract interface from CLI
 #[serde_as]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+#[non_exhaustive]
 pub enum StateTransition {
     Add {
         name: String,
@@ -283,6 +474,13 @@ pub enum StateTransition {
         /// Maximum amount of time intended to spend. Both useful as a time-boxing strategy and as
         /// a backstop to neglected termination.
         time: Duration,
+        #[serde(default)]
+        note: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pauses: Vec<Pause>,
+        /// When participation ended, if before the transition was made.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ended: Option<DateTime<Utc>>,
     },
     Notes {
         name: String,
@@ -304,13 +502,98 @@ pub enum StateTransition {
     Config {
         new_config: UserConfig,
     },
+    StartSession {
+        name: String,
+        #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+        max_time: Duration,
+    },
+    Tag {
+        name: String,
+        tag: String,
+    },
+    Untag {
+        name: String,
+        tag: String,
+    },
+    Weight {
+        name: String,
+        weight: f64,
+    },
+}
+
+impl StateTransition {
+    /// What the transition does, like `log 30m of "steno"`, with time spans in `calendar`.
+    pub const fn display(&self, calendar: Calendar) -> TransitionDisplay<'_> {
+        TransitionDisplay {
+            transition: self,
+            calendar,
+        }
+    }
 }
 
-#[warn(clippy::print_stdout, clippy::print_stderr)] // This shouldn't use cli at all (warning doesn't catch stdin, but if we're printing, something is wrong)
-pub fn handle_transition(state: &mut State, transition: StateTransition) -> Result<()> {
+/// A [``StateTransition``] described for people, see [``StateTransition::display``].
+pub struct TransitionDisplay<'a> {
+    transition: &'a StateTransition,
+    calendar: Calendar,
+}
+
+impl Display for TransitionDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let flat = |duration| FlatTime::in_calendar(duration, self.calendar);
+        match self.transition {
+            StateTransition::Add { name, period } => {
+                write!(f, "add \"{name}\" with period {}", flat(*period).format())
+            }
+            StateTransition::Log {
+                name, time, ended, ..
+            } => {
+                write!(f, "log {} of \"{name}\"", flat(*time).format())?;
+                if let Some(ended) = ended {
+                    let ended = ended.with_timezone(&chrono::Local);
+                    write!(f, " ending {}", ended.format("%Y-%m-%d %H:%M"))?;
+                }
+                Ok(())
+            }
+            StateTransition::Notes { name, .. } => write!(f, "edit notes of \"{name}\""),
+            StateTransition::Remove { name } => write!(f, "remove \"{name}\""),
+            StateTransition::Rename {
+                current_name,
+                new_name,
+            } => write!(f, "rename \"{current_name}\" to \"{new_name}\""),
+            StateTransition::Reset => write!(f, "reset all practices"),
+            StateTransition::EditPeriod { name, new_period } => write!(
+                f,
+                "change period of \"{name}\" to {}",
+                flat(*new_period).format()
+            ),
+            StateTransition::Config { .. } => write!(f, "edit configuration"),
+            StateTransition::StartSession { name, max_time } => write!(
+                f,
+                "start a session of \"{name}\" for up to {}",
+                flat(*max_time).format()
+            ),
+            StateTransition::Tag { name, tag } => write!(f, "tag \"{name}\" with \"{tag}\""),
+            StateTransition::Untag { name, tag } => write!(f, "untag \"{tag}\" from \"{name}\""),
+            StateTransition::Weight { name, weight } => write!(f, "weight \"{name}\" {weight}"),
+        }
+    }
+}
+
+/// Apply a transition to the state as though it happened at `at`.
+///
+/// Given the same state, transition, and time, the result is always the same, which is what lets the
+/// [``Journal``](crate::journal::Journal) replay history.
+#[warn(clippy::print_stdout, clippy::print_stderr)]
+// This shouldn't use cli at all (warning doesn't catch stdin, but if we're printing, something is wrong)
+#[allow(clippy::too_many_lines)]
+pub(crate) fn handle_transition(
+    state: &mut State,
+    transition: StateTransition,
+    at: DateTime<Utc>,
+) -> Result<()> {
     match transition {
         StateTransition::Add { name, period } => {
-            let practice = Practice::new(name.clone(), String::new(), period);
+            let practice = Practice::new(name.clone(), String::new(), period, at);
             match state.practices.entry(practice.name.clone()) {
                 btree_map::Entry::Occupied(_) => {
                     bail!("Practice with name \"{name}\" already exists.")
@@ -319,13 +602,48 @@ pub fn handle_transition(state: &mut State, transition: StateTransition) -> Resu
             };
             Ok(())
         }
-        StateTransition::Log { name, time } => {
+        StateTransition::Log {
+            name,
+            time,
+            note,
+            pauses,
+            ended,
+        } => {
+            ensure!(
+                ended.is_none_or(|ended| ended <= at),
+                "Can't log time ending in the future."
+            );
             let practice = state
                 .practices
                 .get_mut(&name)
                 .context("Practice not found.")?;
-            practice.logged = Utc::now();
-            practice.cumulative = practice.cumulative + time;
+            let end = ended.unwrap_or(at);
+            // time spans can be far longer than there's been time for
+            let start = pauses
+                .iter()
+                .try_fold(Duration::zero(), |sum, pause| {
+                    sum.checked_add(&pause.duration())
+                })
+                .and_then(|paused| end.checked_sub_signed(time)?.checked_sub_signed(paused))
+                .context("Can't log more time than there's been.")?;
+            // completions are kept in order, so logging one from before the latest doesn't move
+            // the period back
+            practice.record(Completion {
+                start,
+                end,
+                duration: time,
+                note,
+                pauses,
+            });
+            // backdated logs are of past sessions, not of one running now, unless they're of the
+            // time since it began (as when stopping one that ran past its max time)
+            let of_session = state
+                .active_session
+                .as_ref()
+                .is_some_and(|session| session.start <= start);
+            if ended.is_none() || of_session {
+                state.end_session_of(&name);
+            }
             Ok(())
         }
         StateTransition::Notes { name, notes } => {
@@ -344,6 +662,7 @@ pub fn handle_transition(state: &mut State, transition: StateTransition) -> Resu
                 }
                 btree_map::Entry::Occupied(entry) => entry.remove(),
             };
+            state.end_session_of(&name);
             Ok(())
         }
         StateTransition::Rename {
@@ -351,25 +670,32 @@ pub fn handle_transition(state: &mut State, transition: StateTransition) -> Resu
             new_name,
         } => {
             ensure!(
-                state.practices.contains_key(&new_name),
-                "Practice with name \"{new_name}\" already exists."
+                state.practices.contains_key(&current_name),
+                "Practice with name \"{current_name}\" not found. (Case sensitive)"
             );
             ensure!(
                 !state.practices.contains_key(&new_name),
                 "Practice with name \"{new_name}\" already exists."
             );
 
-            let practice = state
+            let mut practice = state
                 .practices
                 .remove(&current_name)
                 .expect("we already checked for key membership");
+            practice.name.clone_from(&new_name);
+            if let Some(session) = state
+                .active_session
+                .as_mut()
+                .filter(|session| session.name == current_name)
+            {
+                session.name.clone_from(&new_name);
+            }
             state.practices.insert(new_name, practice);
             Ok(())
         }
         StateTransition::Reset => {
-            let now = Utc::now();
             for practice in state.practices.values_mut() {
-                practice.logged = now;
+                practice.reset = at;
             }
             Ok(())
         }
@@ -385,5 +711,354 @@ pub fn handle_transition(state: &mut State, transition: StateTransition) -> Resu
             state.config.user_config = new_config;
             Ok(())
         }
+        StateTransition::StartSession { name, max_time } => {
+            ensure!(
+                state.practices.contains_key(&name),
+                "Practice with name \"{name}\" not found. (Case sensitive)"
+            );
+            if let Some(session) = &state.active_session {
+                bail!(
+                    "A session of \"{}\" is already running, stop it first.",
+                    session.name
+                );
+            }
+            state.active_session = Some(ActiveSession {
+                name,
+                start: at,
+                max_time,
+            });
+            Ok(())
+        }
+        StateTransition::Tag { name, tag } => {
+            ensure!(!tag.trim().is_empty(), "Tags can't be blank.");
+            let practice = state
+                .practices
+                .get_mut(&name)
+                .context("Practice not found.")?;
+            ensure!(
+                practice.tags.insert(tag.clone()),
+                "\"{name}\" is already tagged with \"{tag}\"."
+            );
+            Ok(())
+        }
+        StateTransition::Untag { name, tag } => {
+            let practice = state
+                .practices
+                .get_mut(&name)
+                .context("Practice not found.")?;
+            ensure!(
+                practice.tags.remove(&tag),
+                "\"{name}\" isn't tagged with \"{tag}\"."
+            );
+            Ok(())
+        }
+        StateTransition::Weight { name, weight } => {
+            ensure!(
+                weight.is_finite() && weight >= 0.0,
+                "Weights must be zero or more."
+            );
+            let practice = state
+                .practices
+                .get_mut(&name)
+                .context("Practice not found.")?;
+            practice.weight = weight;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_transition, State, StateTransition};
+    use crate::clock::ManualClock;
+    use crate::listing::Columns;
+    use crate::testing::add;
+    use crate::utils::BarStyle;
+    use chrono::{DateTime, Duration, Utc};
+    use std::sync::Arc;
+
+    fn state_with(name: &str) -> State {
+        let mut state = State::new();
+        handle_transition(
+            &mut state,
+            StateTransition::Add {
+                name: name.to_owned(),
+                period: Duration::days(1),
+            },
+            Utc::now(),
+        )
+        .unwrap();
+        state
+    }
+
+    #[test]
+    fn log_records_completion() {
+        let mut state = state_with("steno");
+        for minutes in [20, 40] {
+            handle_transition(
+                &mut state,
+                StateTransition::Log {
+                    name: "steno".to_owned(),
+                    time: Duration::minutes(minutes),
+                    note: Some(format!("{minutes} minutes")),
+                    pauses: Vec::new(),
+                    ended: None,
+                },
+                Utc::now(),
+            )
+            .unwrap();
+        }
+
+        let practice = &state.practices["steno"];
+        assert_eq!(practice.completions.len(), 2);
+        assert_eq!(practice.cumulative(), Duration::hours(1));
+        assert_eq!(practice.logged(), practice.completions[1].end);
+        assert_eq!(
+            practice.completions[0].end - practice.completions[0].start,
+            Duration::minutes(20)
+        );
+        assert_eq!(practice.completions[1].note.as_deref(), Some("40 minutes"));
+    }
+
+    #[test]
+    fn backdated_logs_dont_move_logged_back() {
+        let mut state = State::new();
+        let now = Utc::now();
+        let add = StateTransition::Add {
+            name: "steno".to_owned(),
+            period: Duration::days(1),
+        };
+        handle_transition(&mut state, add, now - Duration::days(3)).unwrap();
+        let log = |ended| StateTransition::Log {
+            name: "steno".to_owned(),
+            time: Duration::minutes(30),
+            note: None,
+            pauses: Vec::new(),
+            ended,
+        };
+
+        handle_transition(&mut state, log(Some(now - Duration::hours(2))), now).unwrap();
+        assert_eq!(state.practices["steno"].logged(), now - Duration::hours(2));
+        let start = state.practices["steno"].completions[0].start;
+        assert_eq!(start, now - Duration::hours(2) - Duration::minutes(30));
+
+        // an earlier one is recorded, but the latest still counts
+        handle_transition(&mut state, log(Some(now - Duration::days(1))), now).unwrap();
+        let practice = &state.practices["steno"];
+        assert_eq!(practice.completions.len(), 2);
+        assert_eq!(practice.logged(), now - Duration::hours(2));
+        assert_eq!(practice.cumulative(), Duration::hours(1));
+
+        assert!(handle_transition(&mut state, log(Some(now + Duration::hours(1))), now).is_err());
+    }
+
+    #[test]
+    fn reset_moves_logged_without_completion() {
+        let mut state = state_with("steno");
+        handle_transition(&mut state, StateTransition::Reset, Utc::now()).unwrap();
+
+        let practice = &state.practices["steno"];
+        assert!(practice.completions.is_empty());
+        assert_eq!(practice.logged(), practice.reset);
+    }
+
+    #[test]
+    fn legacy_practice_keeps_logged_and_cumulative() {
+        let legacy = r#"{
+            "config": { "version": "0.1.6", "user_config": { "grace_period_in_seconds": 0 } },
+            "practices": {
+                "steno": {
+                    "created": "2023-08-01T00:00:00Z",
+                    "logged": "2023-08-02T00:00:00Z",
+                    "period": 86400,
+                    "name": "steno",
+                    "notes": "",
+                    "cumulative": 3600
+                }
+            }
+        }"#;
+        let (state, schema) = crate::migration::load_state(legacy).unwrap();
+        assert_eq!(schema, 0);
+
+        let practice = &state.practices["steno"];
+        assert_eq!(practice.logged().to_rfc3339(), "2023-08-02T00:00:00+00:00");
+        assert_eq!(practice.cumulative(), Duration::hours(1));
+        assert!(practice.completions.is_empty());
+        assert_eq!(state.get_user_config().session_cap, Duration::hours(4));
+    }
+
+    #[test]
+    fn logging_ends_active_session() {
+        let mut state = state_with("steno");
+        let start = StateTransition::StartSession {
+            name: "steno".to_owned(),
+            max_time: Duration::hours(1),
+        };
+        handle_transition(&mut state, start.clone(), Utc::now()).unwrap();
+        assert!(handle_transition(&mut state, start.clone(), Utc::now()).is_err());
+
+        let session = state.active_session().unwrap();
+        let (began, stopped) = (session.start, session.start + Duration::days(1));
+        assert_eq!(session.elapsed(stopped), Duration::hours(1));
+        // as `session stop` logs a forgotten session, ending when its max time ran out
+        handle_transition(
+            &mut state,
+            StateTransition::Log {
+                name: "steno".to_owned(),
+                time: Duration::hours(1),
+                note: None,
+                pauses: Vec::new(),
+                ended: Some(began + Duration::hours(1)),
+            },
+            stopped,
+        )
+        .unwrap();
+        assert!(state.active_session().is_none());
+        assert_eq!(state.practices["steno"].completions[0].start, began);
+        assert_eq!(
+            state.practices["steno"].logged(),
+            began + Duration::hours(1)
+        );
+
+        handle_transition(&mut state, start, Utc::now()).unwrap();
+        handle_transition(
+            &mut state,
+            StateTransition::Remove {
+                name: "steno".to_owned(),
+            },
+            Utc::now(),
+        )
+        .unwrap();
+        assert!(state.active_session().is_none());
+    }
+
+    #[test]
+    fn logging_too_much_time_fails() {
+        let mut state = state_with("steno");
+        let log = StateTransition::Log {
+            name: "steno".to_owned(),
+            time: crate::time::parse_time_span("300000y").unwrap(),
+            note: None,
+            pauses: Vec::new(),
+            ended: None,
+        };
+        assert!(handle_transition(&mut state, log, Utc::now()).is_err());
+        assert!(state.practices["steno"].completions.is_empty());
+    }
+
+    #[test]
+    fn listing_huge_periods_doesnt_overflow() {
+        let mut state = State::new();
+        let now = Utc::now();
+        let period = crate::time::parse_time_span("200000000y").unwrap();
+        for name in ["steno", "piano"] {
+            let add = StateTransition::Add {
+                name: name.to_owned(),
+                period,
+            };
+            handle_transition(&mut state, add, now).unwrap();
+        }
+
+        let mut listing = state.listing(now);
+        assert_eq!(listing.danger.period, Duration::MAX);
+        listing.filter(&crate::listing::Filter {
+            due_within: Some(Duration::days(1)),
+            ..Default::default()
+        });
+        assert!(listing.practices.is_empty());
+        assert!(listing
+            .bars(
+                Columns {
+                    cumulative: true,
+                    period: true,
+                    danger: true,
+                    grouped: true
+                },
+                60,
+                BarStyle::Unicode,
+                None
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn rename_follows_active_session() {
+        let mut state = state_with("steno");
+        let rename = |current_name: &str, new_name: &str| StateTransition::Rename {
+            current_name: current_name.to_owned(),
+            new_name: new_name.to_owned(),
+        };
+        let start = StateTransition::StartSession {
+            name: "steno".to_owned(),
+            max_time: Duration::hours(1),
+        };
+        handle_transition(&mut state, start, Utc::now()).unwrap();
+
+        handle_transition(&mut state, rename("steno", "shorthand"), Utc::now()).unwrap();
+        assert!(state.practice("steno").is_none());
+        assert_eq!(state.practice("shorthand").unwrap().name(), "shorthand");
+        assert_eq!(state.active_session().unwrap().name, "shorthand");
+
+        handle_transition(&mut state, add("steno"), Utc::now()).unwrap();
+        assert!(handle_transition(&mut state, rename("steno", "shorthand"), Utc::now()).is_err());
+        assert!(handle_transition(&mut state, rename("piano", "keys"), Utc::now()).is_err());
+    }
+
+    #[test]
+    fn tags_are_added_and_removed_once() {
+        let mut state = state_with("steno");
+        let tag = StateTransition::Tag {
+            name: "steno".to_owned(),
+            tag: "study".to_owned(),
+        };
+        let untag = StateTransition::Untag {
+            name: "steno".to_owned(),
+            tag: "study".to_owned(),
+        };
+
+        handle_transition(&mut state, tag.clone(), Utc::now()).unwrap();
+        assert_eq!(state.get_tags("steno").unwrap(), ["study"]);
+        assert_eq!(state.listing(Utc::now()).practices[0].tags, ["study"]);
+        assert!(handle_transition(&mut state, tag, Utc::now()).is_err());
+
+        handle_transition(&mut state, untag.clone(), Utc::now()).unwrap();
+        assert!(state.get_tags("steno").unwrap().is_empty());
+        assert!(handle_transition(&mut state, untag, Utc::now()).is_err());
+    }
+
+    #[test]
+    fn listing_by_the_state_clock() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        let mut state = State::new();
+        state.set_clock(clock.clone());
+        for (name, days) in [("kanji", 2), ("steno", 1)] {
+            let add = StateTransition::Add {
+                name: name.to_owned(),
+                period: Duration::days(days),
+            };
+            let now = state.now();
+            handle_transition(&mut state, add, now).unwrap();
+        }
+        clock.advance(Duration::hours(18));
+
+        let columns = Columns {
+            cumulative: false,
+            period: true,
+            danger: true,
+            grouped: false,
+        };
+        let bars = state
+            .listing(state.now())
+            .bars(columns, 30, BarStyle::Ascii, None)
+            .unwrap();
+        // 18 hours in, steno is 3/4 of the way through its day and kanji 3/8 through its two
+        assert_eq!(
+            bars,
+            "\n  kanji ######            2d  \
+             \n  steno ############      1d  \
+             \n\
+             \n  danger ########              \n"
+        );
     }
 }
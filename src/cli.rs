@@ -1,8 +1,12 @@
 use std::path::PathBuf;
 
-use super::time::parse_time_span;
 use chrono::Duration;
 use clap::{Parser, Subcommand};
+use prac::listing::{self, DangerStrategy, Format, SortKey};
+use prac::notify::Escape;
+use prac::theme::Preset;
+use prac::time::{parse_time_span, parse_time_span_in, Calendar, TimeSpanError};
+use regex::Regex;
 
 #[derive(Parser, Debug)]
 #[command(author = "Henry Merrilees")]
@@ -13,18 +17,50 @@ pub struct Cli {
     ///
     /// This is useful if you want to track multiple practice sets. I.e. lets say you want to keep
     /// up with your friends.
-    /// alias friends='prac --path=/path/to/friends_state'
+    /// `alias friends='prac --path=/path/to/friends_state'`
     #[arg(long, env = "PRAC_PATH")]
     pub(super) path: Option<PathBuf>,
+    /// Draw bars with `#` rather than `▬`, for terminals whose fonts lack it.
+    #[arg(long, global = true)]
+    pub(super) ascii: bool,
+    /// Act as though it's this time (like `2024-05-01 09:00` or `tomorrow`), to preview what prac
+    /// would show then. Commands which change state refuse it.
+    #[arg(long, global = true, hide = true)]
+    pub(super) now: Option<String>,
     #[command(subcommand)]
     pub(super) command: SubCommand,
 }
 
+/// A time span as given, to be resolved once the statefile says how long months and years are.
+#[derive(Clone, Debug)]
+pub struct Span(String);
+
+impl Span {
+    /// Check that `input` is a time span, so that mistakes are pointed out right away.
+    fn parse(input: &str) -> Result<Self, TimeSpanError> {
+        parse_time_span(input)?;
+        Ok(Self(input.to_owned()))
+    }
+
+    pub fn resolve(&self, calendar: Calendar) -> Result<Duration, TimeSpanError> {
+        parse_time_span_in(&self.0, calendar)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum SubCommand {
     /// List practices w/ progress bars showing time elapsed through period. `help list` for options
     #[command(
-        after_long_help = "-p -c together will display both, but each truncated to the largest unit",
+        after_long_help = "\
+        -p -c together will display both, but each truncated to the largest unit\n\n\
+        The danger bar (-d) sums up progress through every period listed, as set by \
+        `prac config --danger`:\n  \
+        time      total time elapsed over total period, so a practice done yearly counts 365 times \
+        as much as one done daily (the default)\n  \
+        mean      mean fraction of the period elapsed, each practice counting up to a full period\n  \
+        max       fraction of the period elapsed of the most overdue practice\n  \
+        overdue   fraction of practices that are overdue\n  \
+        weighted  like mean, but weighted by each practice's importance (see `prac weight`)",
         alias = "ls"
     )]
     List {
@@ -37,27 +73,74 @@ pub enum SubCommand {
         /// Show "danger bar" that dissplays sum progression through periods.
         #[arg(short, long, default_value = "false")]
         danger: bool,
+        /// Output format. Json always includes the danger aggregate, csv and tsv only with --danger.
+        #[arg(short, long, value_enum, default_value_t)]
+        format: Format,
+        /// Width to draw bars at. Defaults to the terminal's width, or `$COLUMNS` without a
+        /// terminal, or 80 without either.
+        #[arg(short, long)]
+        width: Option<usize>,
+        /// Order to list practices in.
+        #[arg(short, long, value_enum, default_value_t)]
+        sort: SortKey,
+        /// Reverse the order.
+        #[arg(short, long)]
+        reverse: bool,
+        /// Only list practices past their period.
+        #[arg(long)]
+        overdue: bool,
+        /// Only list practices due within a time span (as systemd.time-like time span), or overdue.
+        #[arg(long, value_parser = Span::parse)]
+        due_within: Option<Span>,
+        /// Only list practices whose names match a glob, where `*` is anything and `?` any one
+        /// character.
+        #[arg(long, value_parser = listing::glob, conflicts_with = "regex")]
+        name: Option<Regex>,
+        /// Only list practices whose names match a regex.
+        #[arg(long, value_parser = Regex::new)]
+        regex: Option<Regex>,
+        /// Only list practices with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Don't group tagged practices.
+        #[arg(long)]
+        flat: bool,
     },
     /// Add a new practice.
     Add {
         /// A (unique) name for the practice.
         #[arg(required_unless_present = "interactive")]
         name: Option<String>,
-        /// Anticipated time period between practice sessions (as systemd.time-like time span).
-        #[arg(value_parser = parse_time_span, required_unless_present = "interactive")]
-        period: Option<Duration>,
+        /// Anticipated time period between practice sessions (as systemd.time-like time span, ISO 8601
+        /// duration, or frequency like `weekly` or `twice a week`).
+        #[arg(value_parser = Span::parse, required_unless_present = "interactive")]
+        period: Option<Span>,
         /// Interactive
         #[arg(short, long, default_value = "false")]
         interactive: bool,
     },
-    /// Begin a session of participation in a practice (ctrl-c to end early). Will log time participated automatically.
+    /// Begin a session of participation in a practice (enter or ctrl-c to end early). Will log time participated automatically.
+    ///
+    /// The session is shown as a bar filling towards its max time, above the practice's bar from
+    /// `prac list`. While running, space pauses and resumes (time paused isn't logged), and `a`
+    /// adds a note.
+    ///
+    /// To keep a session going without keeping a terminal open, see `session start`. As such,
+    /// practices can't be named `start`, `stop` or `status`.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Session {
+        #[command(subcommand)]
+        command: Option<SessionCommand>,
         /// Specify practice to log, or leave blank to fuzzy search.
         #[arg(required_unless_present = "interactive")]
         name: Option<String>,
-        /// Time practiced, as systemd.time-like time span.
-        #[arg(value_parser = parse_time_span, requires = "name", required_unless_present = "interactive")]
-        max_time: Option<Duration>,
+        /// Time to practice, as systemd.time-like time span. Leave blank to practice until you
+        /// finish, up to the session cap (see `help config`).
+        #[arg(value_parser = Span::parse, requires = "name")]
+        max_time: Option<Span>,
+        /// Show only the bars, without any times.
+        #[arg(long)]
+        no_clock: bool,
         /// Interactive
         #[arg(short, long, default_value = "false")]
         interactive: bool,
@@ -68,8 +151,28 @@ pub enum SubCommand {
         #[arg(required_unless_present = "interactive")]
         name: Option<String>,
         /// Time practiced, as systemd.time-like time span.
-        #[arg(value_parser = parse_time_span, requires = "name", required_unless_present = "interactive")]
-        time: Option<Duration>,
+        #[arg(
+            value_parser = Span::parse,
+            requires = "name",
+            required_unless_present_any = ["interactive", "from"],
+            conflicts_with = "from"
+        )]
+        time: Option<Span>,
+        /// Note to keep alongside this completion.
+        #[arg(short, long)]
+        note: Option<String>,
+        /// When you finished, if not just now, like `yesterday 18:00` or `2024-05-01 19:30`.
+        #[arg(long, conflicts_with_all = ["ago", "from"])]
+        at: Option<String>,
+        /// How long ago you finished, as systemd.time-like time span.
+        #[arg(long, value_parser = Span::parse, conflicts_with = "from")]
+        ago: Option<Span>,
+        /// When you started, to log the time from then until --to (or now) in place of a time span.
+        #[arg(long)]
+        from: Option<String>,
+        /// When you finished, with --from.
+        #[arg(long, requires = "from")]
+        to: Option<String>,
         /// Interactive
         #[arg(short, long, default_value = "false")]
         interactive: bool,
@@ -91,11 +194,35 @@ pub enum SubCommand {
     Reset,
     /// Show state file location. `help state-location` for more info.
     ///
-    /// State is stored in $PRAC_PATH, [dirs::data_dir]/prac/prac.json
-    /// or [dirs::home_dir]/.prac.json, searched in that order.
+    /// State is stored in `$PRAC_PATH`, `[dirs::data_dir]/prac/prac.json`
+    /// or `[dirs::home_dir]/.prac.json`, searched in that order.
+    ///
+    /// Next to it are kept its journal, checkpoints, and the last few versions of it as
+    /// `<state file>.1`, `<state file>.2`, etc.
     ///
     /// It's a good idea to vcs your state file.
     StateLocation,
+    /// Undo the most recent changes to state.
+    ///
+    /// Anything which changes state can be undone, including `remove` and `reset`, which will bring
+    /// back exactly what was there before.
+    Undo {
+        /// Number of changes to undo.
+        #[arg(default_value = "1")]
+        count: usize,
+    },
+    /// Redo changes reverted by `undo`, provided nothing else has changed since.
+    Redo {
+        /// Number of changes to redo.
+        #[arg(default_value = "1")]
+        count: usize,
+    },
+    /// Rebuild the state file by replaying its journal. `help rebuild` for more info.
+    ///
+    /// Every change to state is recorded in a journal next to the state file, with periodic
+    /// checkpoints of the whole state. If the state file is ever corrupted or lost, this restores
+    /// it from the latest checkpoint and the changes since.
+    Rebuild,
     /// Edit period of practice.
     #[command(alias = "ep")]
     EditPeriod {
@@ -103,8 +230,44 @@ pub enum SubCommand {
         #[arg(required_unless_present = "interactive")]
         name: Option<String>,
         /// Anticipated time period between practice sessions.
-        #[arg(value_parser = parse_time_span, required_unless_present = "interactive")]
-        period: Option<Duration>,
+        #[arg(value_parser = Span::parse, required_unless_present = "interactive")]
+        period: Option<Span>,
+        /// Interactive
+        #[arg(short, long, default_value = "false")]
+        interactive: bool,
+    },
+    /// Tag a practice, grouping it with others in `prac list`. A practice can have many tags.
+    Tag {
+        /// Specify practice to tag, or leave blank to fuzzy search.
+        #[arg(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Tag to add, like "health" or "study".
+        #[arg(requires = "name", required_unless_present = "interactive")]
+        tag: Option<String>,
+        /// Interactive
+        #[arg(short, long, default_value = "false")]
+        interactive: bool,
+    },
+    /// Set how important a practice is, for the weighted danger bar (see `help list`).
+    Weight {
+        /// Specify practice to weight, or leave blank to fuzzy search.
+        #[arg(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Weight relative to other practices, which start at 1. 0 leaves it out.
+        #[arg(requires = "name", required_unless_present = "interactive")]
+        weight: Option<f64>,
+        /// Interactive
+        #[arg(short, long, default_value = "false")]
+        interactive: bool,
+    },
+    /// Remove a tag from a practice.
+    Untag {
+        /// Specify practice to untag, or leave blank to fuzzy search.
+        #[arg(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Tag to remove.
+        #[arg(requires = "name", required_unless_present = "interactive")]
+        tag: Option<String>,
         /// Interactive
         #[arg(short, long, default_value = "false")]
         interactive: bool,
@@ -133,13 +296,74 @@ pub enum SubCommand {
     #[command(after_long_help = "\
         Grace period pads the end of the bars of `prac list` with some extra time to give you a little \
         flexibility and prevent tasks from creeping earlier on each iteration.\n\n\
+        Session cap is the most a session without a max time will log, so one left running overnight \
+        doesn't log the whole night.\n\n\
+        When a session's time is up, prac rings the terminal bell, asks the terminal for a desktop \
+        notification with an escape sequence, and runs the notification hook, if each is configured \
+        (none are by default). \
+        The hook is run with `sh -c`, with `PRAC_PRACTICE` and `PRAC_MESSAGE` set, e.g. \
+        `prac config --notify-hook 'notify-send \"$PRAC_MESSAGE\"'`.\n\n\
+        Theme colors the bars of `prac list` by how far through their period they are. Colors \
+        aren't used when `NO_COLOR` is set or output isn't to a terminal. Individual colors can be \
+        set under `theme` in the statefile.\n\n\
+        Calendar sets how long months and years are in time spans, both those you give and those \
+        prac shows. `approximate` months are 30 days and years 365; `systemd` months are 30.44 \
+        days and years 365.25, as in systemd.time. Periods already set stay as long as they were.\n\n\
         ")]
+    #[command(group(clap::ArgGroup::new("fields").required(true).multiple(true)))]
     Config {
         /// Grace period
-        #[arg(short, long, value_parser = parse_time_span, required_unless_present = "interactive")]
-        grace_period: Option<Duration>,
+        #[arg(short, long, value_parser = Span::parse, group = "fields")]
+        grace_period: Option<Span>,
+        /// Session cap
+        #[arg(short, long, value_parser = Span::parse, group = "fields")]
+        session_cap: Option<Span>,
+        /// Ring the terminal bell when a session's time is up.
+        #[arg(long, group = "fields")]
+        bell: Option<bool>,
+        /// Escape sequence to request a desktop notification with.
+        #[arg(long, value_enum, group = "fields")]
+        notify_escape: Option<Escape>,
+        /// Command to run when a session's time is up. Empty to run none.
+        #[arg(long, group = "fields")]
+        notify_hook: Option<String>,
+        /// Colors for the bars of `prac list`.
+        #[arg(long, value_enum, group = "fields")]
+        theme: Option<Preset>,
+        /// How the danger bar of `prac list` sums up progress (see `help list`).
+        #[arg(long, value_enum, group = "fields")]
+        danger: Option<DangerStrategy>,
+        /// How long months and years are in time spans.
+        #[arg(long, value_enum, group = "fields")]
+        calendar: Option<Calendar>,
         /// Interactive
         #[arg(short, long, default_value = "false")]
         interactive: bool,
     },
 }
+
+/// Sessions which carry on in the background, surviving the terminal they were started in.
+#[derive(Subcommand, Debug)]
+pub enum SessionCommand {
+    /// Start a session in the background and return right away.
+    Start {
+        /// Specify practice to start, or leave blank to fuzzy search.
+        #[arg(required_unless_present = "interactive")]
+        name: Option<String>,
+        /// Most time to log if the session is forgotten, as systemd.time-like time span. Defaults
+        /// to the session cap (see `help config`).
+        #[arg(value_parser = Span::parse, requires = "name")]
+        max_time: Option<Span>,
+        /// Interactive
+        #[arg(short, long, default_value = "false")]
+        interactive: bool,
+    },
+    /// Stop the background session, logging the time since it started (up to its max time).
+    Stop {
+        /// Note to keep alongside this completion.
+        #[arg(short, long)]
+        note: Option<String>,
+    },
+    /// Show how long the background session has been running.
+    Status,
+}
@@ -0,0 +1,76 @@
+//! Where prac gets the time from, so that what depends on it can be tested, or previewed with
+//! `prac --now`.
+//!
+//! A [``State``](crate::application::State) tells the time by the clock set on it, which for
+//! states read through a [``Store``](crate::store::Store) is the store's.
+
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Mutex;
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock, as prac uses unless told otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock stopped at one time.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// A clock which only moves when told to.
+#[derive(Debug)]
+pub struct ManualClock(Mutex<DateTime<Utc>>);
+
+impl ManualClock {
+    pub const fn new(now: DateTime<Utc>) -> Self {
+        Self(Mutex::new(now))
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().expect("clock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().expect("clock poisoned") += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().expect("clock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, FixedClock, ManualClock};
+    use chrono::{DateTime, Duration};
+
+    #[test]
+    fn clocks_tell_the_time_they_are_told() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(FixedClock(start).now(), start);
+
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::hours(2));
+        assert_eq!(clock.now(), start + Duration::hours(2));
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
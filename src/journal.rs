@@ -0,0 +1,501 @@
+//! Append-only record of every [``StateTransition``] applied to a statefile.
+//!
+//! The statefile itself is only ever the latest snapshot of state; the journal (kept alongside it)
+//! is the source of truth it can be rebuilt from. Every [``SNAPSHOT_INTERVAL``] entries, a copy of
+//! the state is kept as a checkpoint so that a rebuild doesn't have to replay everything.
+//!
+//! Undo and redo are recorded as entries of their own rather than by rewriting the journal, so
+//! nothing is ever lost, not even the mistakes.
+
+use crate::application::{handle_transition, State, StateTransition};
+use crate::migration::load_state;
+use crate::store::write_atomically;
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of journal entries between checkpoints.
+pub const SNAPSHOT_INTERVAL: u64 = 50;
+
+/// A change to state, as it was recorded.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    /// Position in the journal, starting from 1.
+    pub seq: u64,
+    /// Time the change was made.
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+/// What a journal [``Entry``] records.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Record {
+    /// A transition was applied.
+    Transition(StateTransition),
+    /// The transition at the given position was reverted.
+    Undo(u64),
+    /// The transition at the given position was reapplied after being undone.
+    Redo(u64),
+}
+
+/// Transition entries, as they stand after undos and redos.
+struct History<'a> {
+    /// Transitions in effect, in the order they were applied.
+    applied: Vec<&'a Entry>,
+    /// Transitions which may be redone, most recently undone last.
+    redoable: Vec<&'a Entry>,
+}
+
+impl<'a> History<'a> {
+    /// Follow undos and redos through the journal.
+    fn new(entries: impl IntoIterator<Item = &'a Entry>) -> Result<Self> {
+        let mut history = Self {
+            applied: Vec::new(),
+            redoable: Vec::new(),
+        };
+        for entry in entries {
+            let (from, to, target) = match entry.record {
+                Record::Transition(_) => {
+                    history.applied.push(entry);
+                    history.redoable.clear();
+                    continue;
+                }
+                Record::Undo(target) => (&mut history.applied, &mut history.redoable, target),
+                Record::Redo(target) => (&mut history.redoable, &mut history.applied, target),
+            };
+            match from.pop() {
+                Some(moved) if moved.seq == target => to.push(moved),
+                _ => bail!(
+                    "journal entry {} does not follow from those before it",
+                    entry.seq
+                ),
+            }
+        }
+        Ok(history)
+    }
+
+    /// Sequence numbers of transitions in effect up to and including `seq`.
+    fn applied_through(&self, seq: u64) -> Vec<u64> {
+        self.applied
+            .iter()
+            .map(|entry| entry.seq)
+            .take_while(|applied| *applied <= seq)
+            .collect()
+    }
+}
+
+/// Journal of the statefile at a given path.
+pub struct Journal {
+    /// Newline-delimited json of [``Entry``]s.
+    path: PathBuf,
+    /// Directory of checkpoints, named by the sequence number they were taken at.
+    snapshot_dir: PathBuf,
+}
+
+impl Journal {
+    /// The journal belonging to the statefile at `state_path`.
+    pub fn new(state_path: &Path) -> Self {
+        Self {
+            path: state_path.with_extension("journal"),
+            snapshot_dir: state_path.with_extension("snapshots"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read every entry in the journal, or none if it hasn't been started.
+    ///
+    /// A final line without a newline was interrupted partway through being written, and since
+    /// its transition was never applied to the statefile, it is ignored.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = std::fs::read_to_string(&self.path).context("could not read journal")?;
+        text.split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("failed to parse journal entry on line {}", i + 1))
+            })
+            .collect()
+    }
+
+    /// Apply `transition` to `state`, and if successful, add it to the journal.
+    ///
+    /// This writes to the journal files straight away, before `state` is saved; use it within
+    /// [``crate::store::Store::update``] so that the two are kept in step.
+    ///
+    /// The first entry in a journal is preceded by a checkpoint of the state it started from, so
+    /// that history from before journaling began isn't lost on rebuild.
+    pub fn apply(&self, state: &mut State, transition: StateTransition) -> Result<()> {
+        if !self.path.exists() {
+            self.checkpoint(state)?;
+        }
+
+        let entry = Entry {
+            seq: state.journal_seq() + 1,
+            at: state.now(),
+            record: Record::Transition(transition.clone()),
+        };
+        handle_transition(state, transition, entry.at)?;
+        self.append(state, &entry)
+    }
+
+    /// The `count` most recent transitions in effect, most recent first.
+    pub fn undoable(&self, count: usize) -> Result<Vec<Entry>> {
+        let entries = self.entries()?;
+        let history = History::new(&entries)?;
+        Ok(history
+            .applied
+            .into_iter()
+            .rev()
+            .take(count)
+            .cloned()
+            .collect())
+    }
+
+    /// The `count` most recently undone transitions, most recent first.
+    pub fn redoable(&self, count: usize) -> Result<Vec<Entry>> {
+        let entries = self.entries()?;
+        let history = History::new(&entries)?;
+        Ok(history
+            .redoable
+            .into_iter()
+            .rev()
+            .take(count)
+            .cloned()
+            .collect())
+    }
+
+    /// Revert the `count` most recent transitions, rebuilding `state` without them.
+    pub fn undo(&self, state: &mut State, count: usize) -> Result<()> {
+        let targets = self.undoable(count)?;
+        ensure!(!targets.is_empty(), "nothing to undo");
+        self.revise(state, targets.iter().map(|entry| Record::Undo(entry.seq)))
+    }
+
+    /// Reapply the `count` most recently undone transitions.
+    pub fn redo(&self, state: &mut State, count: usize) -> Result<()> {
+        let targets = self.redoable(count)?;
+        ensure!(!targets.is_empty(), "nothing to redo");
+        self.revise(state, targets.iter().map(|entry| Record::Redo(entry.seq)))
+    }
+
+    /// Apply any entries missing from `state`, e.g. if prac was interrupted between writing the
+    /// journal and the statefile.
+    pub fn catch_up(&self, state: &mut State) -> Result<()> {
+        let applied = state.journal_seq();
+        let missing = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.seq > applied)
+            .collect::<Vec<_>>();
+        if missing
+            .iter()
+            .all(|entry| matches!(entry.record, Record::Transition(_)))
+        {
+            replay(state, &missing)
+        } else {
+            *state = self.rebuild()?;
+            Ok(())
+        }
+    }
+
+    /// Reconstruct state from the latest usable checkpoint and the transitions in effect since.
+    ///
+    /// A checkpoint is only usable if the transitions it includes are exactly those still in
+    /// effect up to that point, i.e. nothing it includes has since been undone and nothing
+    /// undone at the time has since been redone.
+    pub fn rebuild(&self) -> Result<State> {
+        let entries = self.entries()?;
+        let history = History::new(&entries)?;
+
+        let mut checkpoints = self.checkpoints()?;
+        checkpoints.sort_by_key(|(seq, _)| std::cmp::Reverse(*seq));
+        let mut usable = None;
+        for (seq, path) in checkpoints {
+            let then = History::new(entries.iter().take_while(|entry| entry.seq <= seq))?;
+            if then.applied_through(seq) == history.applied_through(seq) {
+                usable = Some((seq, path));
+                break;
+            }
+        }
+        let (seq, snapshot) = usable.with_context(|| {
+            format!(
+                "no usable checkpoints found in \"{}\" to rebuild from",
+                self.snapshot_dir.display()
+            )
+        })?;
+
+        let (mut state, _) =
+            load_state(&std::fs::read_to_string(&snapshot).context("could not read checkpoint")?)
+                .with_context(|| format!("failed to parse checkpoint \"{}\"", snapshot.display()))?;
+        let since = history
+            .applied
+            .into_iter()
+            .filter(|entry| entry.seq > seq)
+            .cloned()
+            .collect::<Vec<_>>();
+        replay(&mut state, &since)?;
+        state.set_journal_seq(entries.last().map_or(seq, |entry| entry.seq));
+        Ok(state)
+    }
+
+    /// Record undos or redos, then rebuild `state` to match.
+    fn revise(&self, state: &mut State, records: impl IntoIterator<Item = Record>) -> Result<()> {
+        let before = state.journal_seq();
+        for record in records {
+            let entry = Entry {
+                seq: state.journal_seq() + 1,
+                at: state.now(),
+                record,
+            };
+            self.append(state, &entry)?;
+        }
+        let checkpoint_due = before / SNAPSHOT_INTERVAL != state.journal_seq() / SNAPSHOT_INTERVAL;
+        let clock = state.clock();
+        *state = self.rebuild()?;
+        state.set_clock(clock);
+        if checkpoint_due {
+            self.checkpoint(state)?;
+        }
+        Ok(())
+    }
+
+    /// Add an entry to the end of the journal, marking it applied to `state`.
+    fn append(&self, state: &mut State, entry: &Entry) -> Result<()> {
+        state.set_journal_seq(entry.seq);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .context("could not open journal")?;
+        truncate_interrupted(&mut file)?;
+        let mut line = serde_json::to_string(entry).context("failed to serialize entry")?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+            .context("failed to write to journal")?;
+        file.sync_data().context("failed to sync journal")?;
+
+        if matches!(entry.record, Record::Transition(_))
+            && entry.seq.is_multiple_of(SNAPSHOT_INTERVAL)
+        {
+            self.checkpoint(state)?;
+        }
+        Ok(())
+    }
+
+    /// Save a copy of `state` as of its current journal position.
+    fn checkpoint(&self, state: &State) -> Result<()> {
+        std::fs::create_dir_all(&self.snapshot_dir)
+            .context("failed to create checkpoint directory")?;
+        let path = self
+            .snapshot_dir
+            .join(format!("{}.json", state.journal_seq()));
+        let text = serde_json::to_vec_pretty(state).context("failed to serialize checkpoint")?;
+        write_atomically(&path, &text).context("failed to write checkpoint")
+    }
+
+    /// Sequence numbers and paths of all checkpoints.
+    fn checkpoints(&self) -> Result<Vec<(u64, PathBuf)>> {
+        if !self.snapshot_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut checkpoints = Vec::new();
+        for dir_entry in
+            std::fs::read_dir(&self.snapshot_dir).context("could not read checkpoints")?
+        {
+            let path = dir_entry.context("could not read checkpoints")?.path();
+            let seq = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok());
+            if let Some(seq) = seq {
+                checkpoints.push((seq, path));
+            }
+        }
+        Ok(checkpoints)
+    }
+}
+
+/// Cut off a final line left without a newline by an interrupted write, so that the next entry
+/// starts on a line of its own.
+fn truncate_interrupted(file: &mut File) -> Result<()> {
+    let mut last = [0];
+    let intact = file.seek(SeekFrom::End(-1)).is_err()
+        || (file.read_exact(&mut last).is_ok() && last[0] == b'\n');
+    if intact {
+        return Ok(());
+    }
+
+    let mut text = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut text))
+        .context("could not read journal")?;
+    let complete = text
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    if complete != text.len() {
+        file.set_len(complete as u64)
+            .context("failed to truncate journal")?;
+    }
+    Ok(())
+}
+
+/// Apply the transitions of journal entries in order.
+fn replay(state: &mut State, entries: &[Entry]) -> Result<()> {
+    for entry in entries {
+        if let Record::Transition(transition) = &entry.record {
+            handle_transition(state, transition.clone(), entry.at)
+                .with_context(|| format!("failed to replay journal entry {}", entry.seq))?;
+        }
+        state.set_journal_seq(entry.seq);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Journal, SNAPSHOT_INTERVAL};
+    use crate::application::{State, StateTransition};
+    use crate::testing::{add, log, TempDir};
+
+    #[test]
+    fn rebuild_matches_applied_state() {
+        let dir = TempDir::new("rebuild");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        for minutes in 0..SNAPSHOT_INTERVAL + 3 {
+            journal
+                .apply(&mut state, log("steno", minutes.try_into().unwrap()))
+                .unwrap();
+        }
+        assert!(journal.apply(&mut state, log("kierkegaard", 5)).is_err());
+
+        assert_eq!(journal.entries().unwrap().len(), 54);
+        assert_eq!(journal.checkpoints().unwrap().len(), 2);
+        assert_eq!(
+            serde_json::to_value(journal.rebuild().unwrap()).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn catch_up_applies_missing_entries() {
+        let dir = TempDir::new("catch-up");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        let snapshot = serde_json::to_string(&state).unwrap();
+        journal.apply(&mut state, log("steno", 30)).unwrap();
+
+        let mut behind: State = serde_json::from_str(&snapshot).unwrap();
+        journal.catch_up(&mut behind).unwrap();
+        assert_eq!(behind.journal_seq(), 2);
+        assert_eq!(
+            serde_json::to_value(&behind).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn undo_remove_and_reset_restores_state() {
+        let dir = TempDir::new("undo");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        journal.apply(&mut state, log("steno", 30)).unwrap();
+        let before = serde_json::to_value(&state).unwrap();
+
+        journal.apply(&mut state, StateTransition::Reset).unwrap();
+        journal
+            .apply(
+                &mut state,
+                StateTransition::Remove {
+                    name: "steno".to_owned(),
+                },
+            )
+            .unwrap();
+        assert_ne!(serde_json::to_value(&state).unwrap(), before);
+
+        journal.undo(&mut state, 2).unwrap();
+        assert_eq!(state.journal_seq(), 6);
+        state.set_journal_seq(2);
+        assert_eq!(serde_json::to_value(&state).unwrap(), before);
+    }
+
+    #[test]
+    fn redo_reapplies_until_something_else_changes() {
+        let dir = TempDir::new("redo");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        journal.apply(&mut state, log("steno", 30)).unwrap();
+        let logged = serde_json::to_value(&state).unwrap();
+
+        journal.undo(&mut state, 1).unwrap();
+        assert_eq!(journal.redoable(5).unwrap().len(), 1);
+        journal.redo(&mut state, 1).unwrap();
+        state.set_journal_seq(2);
+        assert_eq!(serde_json::to_value(&state).unwrap(), logged);
+
+        journal.undo(&mut state, 2).unwrap();
+        assert!(journal.undoable(1).unwrap().is_empty());
+        journal.apply(&mut state, add("kierkegaard")).unwrap();
+        assert!(journal.redoable(1).unwrap().is_empty());
+        assert!(journal.redo(&mut state, 1).is_err());
+    }
+
+    #[test]
+    fn rebuild_skips_checkpoints_including_undone_transitions() {
+        let dir = TempDir::new("undo-checkpoint");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        for _ in 1..SNAPSHOT_INTERVAL {
+            journal.apply(&mut state, log("steno", 1)).unwrap();
+        }
+        assert_eq!(journal.checkpoints().unwrap().len(), 2);
+
+        journal.undo(&mut state, 3).unwrap();
+        let rebuilt = journal.rebuild().unwrap();
+        assert_eq!(
+            serde_json::to_value(&rebuilt).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+        assert_eq!(journal.undoable(usize::MAX).unwrap().len(), 47);
+    }
+
+    #[test]
+    fn interrupted_entry_is_discarded() {
+        let dir = TempDir::new("interrupted");
+        let journal = Journal::new(&dir.state_path());
+        let mut state = State::new();
+        journal.apply(&mut state, add("steno")).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(journal.path())
+            .unwrap();
+        std::io::Write::write_all(&mut file, br#"{"seq":2,"at":"#).unwrap();
+
+        assert_eq!(journal.entries().unwrap().len(), 1);
+        journal.apply(&mut state, log("steno", 30)).unwrap();
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].seq, 2);
+    }
+}
@@ -0,0 +1,67 @@
+//! The data model behind the `prac` command, for building other tools on the same statefile: a
+//! status-bar widget, a bot, a dashboard.
+//!
+//! - [``store::Store``] loads and saves the statefile, safely alongside any running `prac`.
+//! - [``application::State``] holds every [``application::Practice``], and is changed by applying
+//!   an [``application::StateTransition``] through the [``journal::Journal``], so that it can be
+//!   undone. Applying writes to the journal files kept beside the statefile (`<name>.journal` and
+//!   `<name>.snapshots/`), so there is no way to change a state without a statefile to go with it.
+//! - [``application::State::listing``] gives each practice's progress through its period, as
+//!   `prac list` shows it.
+//! - [``time``] parses and formats time spans like `1w 2d` or `1:30`.
+//! - [``clock``] sets what time a state thinks it is, for tests or previews.
+//!
+//! Nothing here prints or prompts; that is left to the `prac` binary (and to you).
+//!
+//! ```no_run
+//! use chrono::Utc;
+//! use prac::application::{State, StateTransition};
+//! use prac::store::Store;
+//!
+//! let store = Store::new(State::get_path()?);
+//! let state = store.load()?.state;
+//!
+//! for row in state.listing(Utc::now()).practices {
+//!     println!("{}: {:.0}%", row.name, row.fraction * 100.0);
+//! }
+//!
+//! // as `prac log steno 30min` would
+//! let time = prac::time::parse_time_span_in("30min", state.get_user_config().calendar)?;
+//! let log = StateTransition::Log {
+//!     name: "steno".to_owned(),
+//!     time,
+//!     note: None,
+//!     pauses: Vec::new(),
+//!     ended: None,
+//! };
+//! store.update(|state, journal| journal.apply(state, log))?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::style,
+    clippy::complexity,
+    clippy::perf
+)]
+#![cfg_attr(not(test), warn(clippy::print_stdout, clippy::print_stderr))]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate
+)]
+
+pub mod application;
+pub mod clock;
+pub mod journal;
+pub mod listing;
+pub mod migration;
+pub mod notify;
+pub mod store;
+#[cfg(test)]
+mod testing;
+pub mod theme;
+pub mod time;
+pub mod utils;
@@ -0,0 +1,709 @@
+//! What `prac list` shows, drawn as bars for people or written as json, csv or tsv for other
+//! programs.
+
+use crate::theme::Theme;
+use crate::time::{saturating_add, saturating_sub, Calendar, FlatTime};
+use crate::utils::{overflow_bar, BarStyle};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Most periods bars are stretched to show, so one long-forgotten practice doesn't squash the rest.
+pub const MAX_SCALE: f64 = 3.0;
+
+/// How `prac list` is written out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum Format {
+    /// A bar per practice, sized to the terminal.
+    #[default]
+    Bars,
+    Json,
+    Csv,
+    Tsv,
+}
+
+/// A practice's progress through its period.
+#[serde_as]
+#[derive(Serialize)]
+pub struct Row {
+    pub name: String,
+    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde(rename = "period_seconds")]
+    pub period: Duration,
+    /// Time since last logged (or reset).
+    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde(rename = "elapsed_seconds")]
+    pub elapsed: Duration,
+    /// Fraction of the period, plus grace period, elapsed. Over 1 once overdue.
+    pub fraction: f64,
+    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde(rename = "cumulative_seconds")]
+    pub cumulative: Duration,
+    pub created: DateTime<Utc>,
+    pub logged: DateTime<Utc>,
+    pub tags: Vec<String>,
+    /// Importance, for the weighted danger strategy.
+    pub weight: f64,
+}
+
+/// How the danger bar sums up progress through several periods.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DangerStrategy {
+    /// Total time elapsed over total period, so longer periods count for more.
+    #[default]
+    Time,
+    /// Mean fraction of the period elapsed, each counting up to 1.
+    Mean,
+    /// Fraction of the period elapsed of the furthest along practice.
+    Max,
+    /// Fraction of practices that are overdue.
+    Overdue,
+    /// Like mean, weighted by each practice's weight (see `prac weight`).
+    Weighted,
+}
+
+/// Progress through all periods together.
+#[serde_as]
+#[derive(Serialize)]
+pub struct Danger {
+    pub strategy: DangerStrategy,
+    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde(rename = "elapsed_seconds")]
+    pub elapsed: Duration,
+    /// Sum of periods, including grace periods.
+    #[serde_as(as = "DurationSeconds<i64>")]
+    #[serde(rename = "period_seconds")]
+    pub period: Duration,
+    /// Number of practices past their period.
+    pub overdue: usize,
+    /// Summed up as by [``Danger::strategy``].
+    pub fraction: f64,
+}
+
+impl Row {
+    /// Time left until the end of the period (plus `grace_period`), negative once overdue.
+    fn remaining(&self, grace_period: Duration) -> Duration {
+        saturating_sub(saturating_add(self.period, grace_period), self.elapsed)
+    }
+}
+
+impl Danger {
+    fn of<'a>(
+        practices: impl IntoIterator<Item = &'a Row>,
+        grace_period: Duration,
+        strategy: DangerStrategy,
+    ) -> Self {
+        let practices = practices.into_iter().collect::<Vec<_>>();
+        let elapsed = practices.iter().fold(Duration::zero(), |sum, row| {
+            saturating_add(sum, row.elapsed)
+        });
+        let period = practices.iter().fold(Duration::zero(), |sum, row| {
+            saturating_add(sum, saturating_add(row.period, grace_period))
+        });
+        let overdue = practices.iter().filter(|row| row.fraction >= 1.0).count();
+
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = |numerator: f64, denominator: f64| {
+            if denominator == 0.0 {
+                0.0
+            } else {
+                numerator / denominator
+            }
+        };
+        let clamped = |row: &Row| row.fraction.clamp(0.0, 1.0);
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = match strategy {
+            DangerStrategy::Time => {
+                ratio(elapsed.num_seconds() as f64, period.num_seconds() as f64)
+            }
+            DangerStrategy::Mean => ratio(
+                practices.iter().map(|row| clamped(row)).sum(),
+                practices.len() as f64,
+            ),
+            DangerStrategy::Max => practices.iter().map(|row| row.fraction).fold(0.0, f64::max),
+            DangerStrategy::Overdue => ratio(overdue as f64, practices.len() as f64),
+            DangerStrategy::Weighted => ratio(
+                practices.iter().map(|row| row.weight * clamped(row)).sum(),
+                practices.iter().map(|row| row.weight).sum(),
+            ),
+        };
+
+        Self {
+            strategy,
+            elapsed,
+            period,
+            overdue,
+            fraction,
+        }
+    }
+}
+
+/// Practices, as of a moment.
+#[derive(Serialize)]
+pub struct Listing {
+    pub practices: Vec<Row>,
+    /// Over the practices listed, so it follows any filter.
+    pub danger: Danger,
+    #[serde(skip)]
+    grace_period: Duration,
+    #[serde(skip)]
+    strategy: DangerStrategy,
+    /// How long months and years are in the times drawn.
+    #[serde(skip)]
+    calendar: Calendar,
+    /// Whether some practices have been filtered out.
+    #[serde(skip)]
+    filtered: bool,
+    /// The only tag listed, if filtered to one.
+    #[serde(skip)]
+    tag: Option<String>,
+}
+
+/// What to order practices by.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum SortKey {
+    #[default]
+    Name,
+    /// Fraction of the period elapsed, least first.
+    Fraction,
+    /// Time since last logged, least first.
+    Elapsed,
+    Period,
+    Cumulative,
+    Created,
+}
+
+/// Which practices to list. Practices must match every part given.
+#[derive(Default)]
+pub struct Filter {
+    /// Only those past their period.
+    pub overdue: bool,
+    /// Only those whose period ends within this long, including any already overdue.
+    pub due_within: Option<Duration>,
+    /// Only those whose names match.
+    pub name: Option<Regex>,
+    /// Only those with this tag.
+    pub tag: Option<String>,
+}
+
+/// A pattern matching names which match `glob` as a whole, where `*` is any run of characters and
+/// `?` any one.
+pub fn glob(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+}
+
+/// What to show alongside the bars.
+#[derive(Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Columns {
+    pub cumulative: bool,
+    pub period: bool,
+    pub danger: bool,
+    /// Group practices by tag (if any are tagged), each with its own danger bar.
+    pub grouped: bool,
+}
+
+impl Listing {
+    pub fn new(
+        practices: Vec<Row>,
+        grace_period: Duration,
+        strategy: DangerStrategy,
+        calendar: Calendar,
+    ) -> Self {
+        Self {
+            danger: Danger::of(&practices, grace_period, strategy),
+            practices,
+            grace_period,
+            strategy,
+            calendar,
+            filtered: false,
+            tag: None,
+        }
+    }
+
+    pub fn sort(&mut self, key: SortKey, reverse: bool) {
+        match key {
+            SortKey::Name => self.practices.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortKey::Fraction => self
+                .practices
+                .sort_by(|a, b| a.fraction.total_cmp(&b.fraction)),
+            SortKey::Elapsed => self.practices.sort_by_key(|row| row.elapsed),
+            SortKey::Period => self.practices.sort_by_key(|row| row.period),
+            SortKey::Cumulative => self.practices.sort_by_key(|row| row.cumulative),
+            SortKey::Created => self.practices.sort_by_key(|row| row.created),
+        }
+        if reverse {
+            self.practices.reverse();
+        }
+    }
+
+    /// Keep only the practices `filter` matches.
+    pub fn filter(&mut self, filter: &Filter) {
+        let grace_period = self.grace_period;
+        self.practices.retain(|row| {
+            (!filter.overdue || row.fraction >= 1.0)
+                && filter
+                    .due_within
+                    .is_none_or(|within| row.remaining(grace_period) <= within)
+                && filter
+                    .name
+                    .as_ref()
+                    .is_none_or(|name| name.is_match(&row.name))
+                && filter.tag.as_ref().is_none_or(|tag| row.tags.contains(tag))
+        });
+        self.danger = Danger::of(&self.practices, grace_period, self.strategy);
+        self.filtered = filter.overdue
+            || filter.due_within.is_some()
+            || filter.name.is_some()
+            || filter.tag.is_some();
+        self.tag.clone_from(&filter.tag);
+    }
+
+    /// Bars `width` columns wide, as `prac list` draws them, colored by `theme` if given.
+    ///
+    /// Bars share a scale, stretched past one period (up to [``MAX_SCALE``]) to fit the most overdue
+    /// practice, with the end of the period marked on those which haven't reached it.
+    pub fn bars(
+        &self,
+        columns: Columns,
+        width: usize,
+        style: BarStyle,
+        theme: Option<&Theme>,
+    ) -> Result<String> {
+        if self.practices.is_empty() {
+            let message = if self.filtered {
+                "No practices match.\n"
+            } else {
+                "You don't have any practices yet. Add some with `prac add`.\n"
+            };
+            return Ok(message.to_owned());
+        }
+
+        let start_messages = self
+            .practices
+            .iter()
+            .map(|row| format!("  {} ", row.name))
+            .collect::<Vec<_>>();
+
+        let end_messages = self
+            .practices
+            .iter()
+            .map(|row| {
+                let period_time = FlatTime::in_calendar(row.period, self.calendar);
+                let cumulative_time = FlatTime::in_calendar(row.cumulative, self.calendar);
+
+                match (columns.cumulative, columns.period) {
+                    (true, true) => format!(
+                        " {} c / {} p  ",
+                        cumulative_time.format_abbreviated(),
+                        period_time.format_abbreviated(),
+                    ),
+                    (true, false) => {
+                        format!(" {}  ", cumulative_time.format())
+                    }
+                    (false, true) => format!(" {}  ", period_time.format()),
+                    (false, false) => "  ".to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let max_start_len = start_messages.iter().map(String::len).max().unwrap_or(0);
+        let max_end_len = end_messages.iter().map(String::len).max().unwrap_or(0);
+
+        let padding_width = max_start_len + max_end_len;
+        let bar_width = width.checked_sub(padding_width).with_context(|| {
+            format!("term width {width} too small, must be at least {padding_width}")
+        })?;
+
+        let danger = columns.danger.then_some(self.danger.fraction);
+        let scale = self
+            .practices
+            .iter()
+            .map(|row| row.fraction)
+            .chain(danger)
+            .fold(1.0, f64::max)
+            .min(MAX_SCALE);
+        let bar = |fraction: f64| {
+            let (filled, rest) = overflow_bar(bar_width, fraction, scale, style);
+            let filled = match theme {
+                Some(theme) => theme.color(fraction).paint(&filled),
+                None => filled,
+            };
+            filled + &rest
+        };
+
+        let line = |start: &str, fraction: f64, end: &str| {
+            format!(
+                "{start:>max_start_len$}{}{end:<max_end_len$}\n",
+                bar(fraction)
+            )
+        };
+
+        let mut out = String::from("\n");
+        if columns.grouped && self.practices.iter().any(|row| !row.tags.is_empty()) {
+            for (group, members) in self.groups() {
+                let _ = writeln!(out, " {group}");
+                for &i in &members {
+                    out.push_str(&line(
+                        &start_messages[i],
+                        self.practices[i].fraction,
+                        &end_messages[i],
+                    ));
+                }
+                let danger = Danger::of(
+                    members.iter().map(|&i| &self.practices[i]),
+                    self.grace_period,
+                    self.strategy,
+                );
+                out.push_str(&line("  danger ", danger.fraction, ""));
+                out.push('\n');
+            }
+        } else {
+            for (row, start, end) in
+                itertools::izip!(&self.practices, &start_messages, &end_messages)
+            {
+                out.push_str(&line(start, row.fraction, end));
+            }
+            out.push('\n');
+        }
+
+        if let Some(fraction) = danger {
+            out.push_str(&line("  danger ", fraction, ""));
+        }
+
+        Ok(out)
+    }
+
+    /// Practices by tag, as indices into the listing, followed by any untagged practices. A
+    /// practice with several tags is in each of their groups.
+    fn groups(&self) -> Vec<(String, Vec<usize>)> {
+        let tags = self.tag.as_ref().map_or_else(
+            || {
+                self.practices
+                    .iter()
+                    .flat_map(|row| &row.tags)
+                    .collect::<BTreeSet<_>>()
+            },
+            |tag| BTreeSet::from([tag]),
+        );
+        let members = |tagged: &dyn Fn(&Row) -> bool| {
+            (0..self.practices.len())
+                .filter(|&i| tagged(&self.practices[i]))
+                .collect::<Vec<_>>()
+        };
+
+        let mut groups = tags
+            .into_iter()
+            .map(|tag| (tag.clone(), members(&|row| row.tags.contains(tag))))
+            .collect::<Vec<_>>();
+        let untagged = members(&|row| row.tags.is_empty());
+        if !untagged.is_empty() {
+            groups.push(("(untagged)".to_owned(), untagged));
+        }
+        groups
+    }
+
+    pub fn json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("failed to serialize list")
+    }
+
+    /// A header and a line per practice, with fields separated by `separator` and tags by `;`. With
+    /// `danger`, the aggregate is added as a last line named `danger`, without created and logged
+    /// times.
+    pub fn delimited(&self, separator: char, danger: bool) -> String {
+        let field = |value: &str| {
+            if separator == ',' {
+                if value.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", value.replace('"', "\"\""))
+                } else {
+                    value.to_owned()
+                }
+            } else {
+                value.replace([separator, '\n', '\r'], " ")
+            }
+        };
+        let line = |fields: &[String]| {
+            let mut line = fields.join(&separator.to_string());
+            line.push('\n');
+            line
+        };
+
+        let mut out = line(
+            &[
+                "name",
+                "period_seconds",
+                "elapsed_seconds",
+                "fraction",
+                "cumulative_seconds",
+                "created",
+                "logged",
+                "tags",
+                "weight",
+            ]
+            .map(str::to_owned),
+        );
+        for row in &self.practices {
+            out.push_str(&line(&[
+                field(&row.name),
+                row.period.num_seconds().to_string(),
+                row.elapsed.num_seconds().to_string(),
+                row.fraction.to_string(),
+                row.cumulative.num_seconds().to_string(),
+                row.created.to_rfc3339(),
+                row.logged.to_rfc3339(),
+                field(&row.tags.join(";")),
+                row.weight.to_string(),
+            ]));
+        }
+        if danger {
+            out.push_str(&line(&[
+                "danger".to_owned(),
+                self.danger.period.num_seconds().to_string(),
+                self.danger.elapsed.num_seconds().to_string(),
+                self.danger.fraction.to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ]));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob, Columns, DangerStrategy, Filter, Listing, Row, SortKey};
+    use crate::theme::{Color, Preset};
+    use crate::time::Calendar;
+    use crate::utils::BarStyle;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn listing() -> Listing {
+        let created = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap();
+        Listing::new(
+            vec![
+                Row {
+                    name: "steno".to_owned(),
+                    period: Duration::days(1),
+                    elapsed: Duration::hours(12),
+                    fraction: 0.5,
+                    cumulative: Duration::hours(3),
+                    created,
+                    logged: created,
+                    tags: vec!["study".to_owned()],
+                    weight: 1.0,
+                },
+                Row {
+                    name: "scales, arpeggios".to_owned(),
+                    period: Duration::days(2),
+                    elapsed: Duration::days(3),
+                    fraction: 1.5,
+                    cumulative: Duration::zero(),
+                    created,
+                    logged: created,
+                    tags: vec!["music".to_owned()],
+                    weight: 3.0,
+                },
+            ],
+            Duration::zero(),
+            DangerStrategy::Time,
+            Calendar::default(),
+        )
+    }
+
+    #[test]
+    fn json_has_practices_and_danger() {
+        let json: serde_json::Value = serde_json::from_str(&listing().json().unwrap()).unwrap();
+        assert_eq!(json["practices"][0]["name"], "steno");
+        assert_eq!(json["practices"][0]["period_seconds"], 86400);
+        assert_eq!(json["practices"][1]["fraction"], 1.5);
+        assert_eq!(json["practices"][0]["logged"], "2023-08-01T00:00:00Z");
+        assert_eq!(json["danger"]["period_seconds"], 3 * 86400);
+    }
+
+    #[test]
+    fn delimited_fields_are_escaped() {
+        let csv = listing().delimited(',', true);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("steno,86400,43200,0.5,10800,2023-08-01T00:00:00+00:00,"));
+        assert!(lines[2].starts_with("\"scales, arpeggios\",172800,"));
+        assert_eq!(
+            lines[3],
+            format!("danger,259200,302400,{},,,,,", 84.0 / 72.0)
+        );
+
+        let tsv = listing().delimited('\t', false);
+        assert_eq!(tsv.lines().count(), 3);
+        assert!(tsv.lines().all(|line| line.split('\t').count() == 9));
+    }
+
+    #[test]
+    fn bars_fill_width() {
+        let columns = Columns {
+            cumulative: true,
+            period: true,
+            danger: true,
+            grouped: false,
+        };
+        let bars = listing()
+            .bars(columns, 80, BarStyle::Unicode, None)
+            .unwrap();
+        let lines = bars
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.chars().count() == 80));
+        assert!(listing()
+            .bars(columns, 10, BarStyle::Unicode, None)
+            .is_err());
+
+        let ascii = listing().bars(columns, 80, BarStyle::Ascii, None).unwrap();
+        assert!(ascii.is_ascii(), "{ascii}");
+    }
+
+    #[test]
+    fn bars_show_urgency_and_overflow() {
+        let columns = Columns {
+            cumulative: false,
+            period: false,
+            danger: false,
+            grouped: false,
+        };
+        let theme = Preset::Traffic.theme();
+        let bars = listing()
+            .bars(columns, 40, BarStyle::Ascii, Some(&theme))
+            .unwrap();
+        let lines = bars
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+
+        // steno is half through its period, on a scale stretched to 1.5 periods by the overdue row.
+        assert!(
+            lines[0].contains(&Color::Green.paint(&"#".repeat(6))),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[0].contains('|'), "{}", lines[0]);
+        assert!(lines[1].contains(&Color::Red.paint(&format!(
+            "{}{}",
+            "#".repeat(12),
+            "+".repeat(6)
+        ))));
+    }
+
+    #[test]
+    fn sorting_and_filtering() {
+        fn names(listing: &Listing) -> Vec<&str> {
+            listing
+                .practices
+                .iter()
+                .map(|row| row.name.as_str())
+                .collect()
+        }
+        let mut sorted = listing();
+        sorted.sort(SortKey::Fraction, true);
+        assert_eq!(names(&sorted), ["scales, arpeggios", "steno"]);
+        sorted.sort(SortKey::Cumulative, false);
+        assert_eq!(names(&sorted), ["scales, arpeggios", "steno"]);
+        sorted.sort(SortKey::Elapsed, false);
+        assert_eq!(names(&sorted), ["steno", "scales, arpeggios"]);
+
+        let filtered = |filter: Filter| {
+            let mut listing = listing();
+            listing.filter(&filter);
+            listing
+        };
+        let overdue = filtered(Filter {
+            overdue: true,
+            ..Filter::default()
+        });
+        assert_eq!(names(&overdue), ["scales, arpeggios"]);
+        assert_eq!(overdue.danger.period, Duration::days(2));
+        let due = filtered(Filter {
+            due_within: Some(Duration::hours(12)),
+            ..Filter::default()
+        });
+        assert_eq!(names(&due), ["steno", "scales, arpeggios"]);
+        let due = filtered(Filter {
+            due_within: Some(Duration::hours(11)),
+            ..Filter::default()
+        });
+        assert_eq!(names(&due), ["scales, arpeggios"]);
+        let globbed = filtered(Filter {
+            name: Some(glob("st?n*").unwrap()),
+            ..Filter::default()
+        });
+        assert_eq!(names(&globbed), ["steno"]);
+        assert!(!glob("sten").unwrap().is_match("steno"));
+        assert!(glob("scales, *").unwrap().is_match("scales, arpeggios"));
+    }
+
+    #[test]
+    fn grouped_by_tag() {
+        let columns = Columns {
+            cumulative: false,
+            period: false,
+            danger: false,
+            grouped: true,
+        };
+        let mut listing = listing();
+        listing.practices[1].tags.push("study".to_owned());
+        let bars = listing.bars(columns, 60, BarStyle::Ascii, None).unwrap();
+        let headers = bars
+            .lines()
+            .filter(|line| line.starts_with(' ') && !line.starts_with("  "))
+            .collect::<Vec<_>>();
+        assert_eq!(headers, [" music", " study"]);
+        assert_eq!(bars.matches("danger").count(), 2);
+
+        listing.filter(&Filter {
+            tag: Some("music".to_owned()),
+            ..Filter::default()
+        });
+        let bars = listing.bars(columns, 60, BarStyle::Ascii, None).unwrap();
+        assert!(bars.contains(" music\n"), "{bars}");
+        assert!(!bars.contains("study"), "{bars}");
+        assert!(!bars.contains("steno"), "{bars}");
+    }
+
+    #[test]
+    fn danger_strategies() {
+        let danger = |strategy| {
+            let rows = listing().practices;
+            Listing::new(rows, Duration::zero(), strategy, Calendar::default()).danger
+        };
+        // steno is half through a day, scales one and a half through two days (weighted 3 to 1).
+        assert!((danger(DangerStrategy::Time).fraction - 84.0 / 72.0).abs() < 1e-9);
+        assert!((danger(DangerStrategy::Mean).fraction - 0.75).abs() < 1e-9);
+        assert!((danger(DangerStrategy::Max).fraction - 1.5).abs() < 1e-9);
+        assert!((danger(DangerStrategy::Overdue).fraction - 0.5).abs() < 1e-9);
+        assert!((danger(DangerStrategy::Weighted).fraction - 3.5 / 4.0).abs() < 1e-9);
+        assert_eq!(danger(DangerStrategy::Overdue).overdue, 1);
+        let empty = Listing::new(
+            Vec::new(),
+            Duration::zero(),
+            DangerStrategy::Mean,
+            Calendar::default(),
+        );
+        assert!(empty.danger.fraction.abs() < 1e-9);
+    }
+}
@@ -72,12 +72,25 @@
 //!
 //! > Hint: configure your shell config to `prac list` on first prompt to be reminded of your priorities!
 //!
+//! For status bars and dashboards, `prac list --format json` (or `csv`, `tsv`) gives the same
+//! progress in a form other programs can read.
+//!
+//! Practices can be grouped with tags, e.g. `prac tag steno study`. `prac list` then shows each group
+//! with its own danger bar, and `prac list --tag study` only that group.
+//!
 //! To begin a session of a particular task, we use `prac session...`. I recommended `prac session -i` for interactive mode. We also should specify how long we'd like to practice for.
 //! ```bash
 //! ♥  prac session steno 2h
 //! ```
-//! Prac will then keep an eye on how long it's been. Optionally, using a terminal with job-completion
-//! notifications will ping you after the desired participation duration has elapsed.
+//! Prac will then keep an eye on how long it's been. To be told once the desired participation
+//! duration has elapsed, turn on the terminal bell (`prac config --bell true`), a desktop notification
+//! (`prac config --notify-escape osc9`), or a hook of your own (see `prac help config`).
+//! If you would rather not keep a terminal open, `prac session start steno 2h` carries on in the
+//! background until `prac session stop`.
+//! To practice until stuck instead, leave off the time (`prac session steno`) and finish with enter.
+//! Practiced away from the terminal? `prac log steno 45min --ago 3h` (or `--at`, or `--from` and
+//! `--to`) logs it for when it happened, written like `yesterday 18:00`, `last monday 9am`, `3h ago`
+//! or `2024-05-01 19:30`.
 //!
 //!
 //!
@@ -100,8 +113,8 @@
 //! would have done it. That interface would have been a lot easier.
 //!
 //! > Hint: consider, in addition to longer blocks, scheduling a dedicated "prac storm" of 1-2
-//! hours, in which practices are attempted only if they may be reasonably kept under 10-15 minutes.
-//! Then hold yourself to it.
+//! > hours, in which practices are attempted only if they may be reasonably kept under 10-15 minutes.
+//! > Then hold yourself to it.
 //!
 //! If you are a reflective person, `prac list --cumulative` also shows you how much time you have
 //! given total to each item, enabling you to take pride in your work, and to adjust your
@@ -113,6 +126,8 @@
 //! The `-d` flag on `prac list -d` adds a "danger bar" which is a weighted sum display of all
 //! practice periods. You should consider set your periods to where it is achievable to keep the
 //! danger bar under halfway-full.
+//! By default it weighs practices by their periods; `prac config --danger` picks another way of
+//! summing up (see `prac help list`), such as by importance set with `prac weight steno 2`.
 //!
 //! If you are comfortable using a terminal editor, you should record goals, progress, and whatever
 //! else with `prac notes`. This opens ``$EDITOR``, which often defaults to vi. If this is
@@ -138,6 +153,7 @@
 //!
 //! If you get way behind on everything, no need to give up, just `prac reset` to start again with
 //! a clean slate. Prac is intentionally designed to avoid any derailing events.
+//! Likewise, if you log or remove something by mistake, `prac undo` puts things back as they were.
 //!
 //! Nominally an alternative to routine-scheduling systems, *prac is secretly a routine-discovery system.*
 //! Daily practices will near the end of their period at a similar time as they were completed the
@@ -164,6 +180,11 @@
 //! 3days15hours # combined quantities
 //! 1w4d         # abbreviations
 //! 4M           # just be careful... M is month, m is minute
+//! 1.5h         # decimals, and .5d for half a day
+//! 1:30         # clock-style H:MM, or H:MM:SS like 0:45:30
+//! P1W3D        # ISO 8601, and PT45M for 45 minutes, each on its own
+//! weekly       # named frequencies: hourly, daily, every other day, twice a week,
+//!              # fortnightly, twice a month, monthly, quarterly, yearly... also on their own
 //! ```
 //! Intermediate whitespace is permessible, but you still need quotes in the cli (outside of
 //! interactive mode) so as to be captured as a single argument.
@@ -181,9 +202,13 @@
 //! ```
 //! See [src/time/time.pest](https://github.com/henry-merrilees/prac/blob/main/src/time/time.pest) for the complete grammar.
 //! Errors are decent enough to help you if you get stuck.
-
-// TODO: --no-clock for sessions, and make max_time optional. Display bar, and optional ENTER
-// handler to terminate.
+//! Months are 30 days and years 365, unless you'd rather have systemd.time's 30.44 and 365.25
+//! with `prac config --calendar systemd`.
+//!
+//! ## As a library
+//! Everything prac knows is also available as the `prac` library crate: loading and saving the
+//! statefile, applying changes, how far through their periods practices are, and time spans.
+//! It never prints or prompts, so it's fit for a status-bar widget or a bot.
 
 #![warn(
     clippy::all,
@@ -191,44 +216,270 @@
     clippy::nursery,
     clippy::style,
     clippy::complexity,
-    clippy::perf,
-    clippy::style
+    clippy::perf
 )]
 
-mod application;
 mod cli;
-mod time;
-mod utils;
+mod session;
 
-use anyhow::{bail, Context, Result};
-use application::{handle_transition, State, StateTransition};
-use clap::Parser;
-use cli::{Cli, SubCommand};
-use std::io::{BufWriter, Write};
-use std::path::Path;
-use std::sync::atomic::AtomicBool;
+use anyhow::{bail, ensure, Context, Result};
+use application::{State, StateTransition};
+use clap::{Parser, Subcommand};
+use cli::{Cli, SessionCommand, SubCommand};
+use listing::Format;
+use prac::clock::{Clock, FixedClock};
+use prac::{application, journal, listing, migration, store, theme, time, utils};
 use std::sync::Arc;
+use store::Store;
+use utils::BarStyle;
 
-fn get_time_span_interactive(msg: &str) -> Result<chrono::Duration> {
+fn get_time_span_interactive(msg: &str, calendar: time::Calendar) -> Result<chrono::Duration> {
     let time_input = dialoguer::Input::<String>::new()
         .with_prompt(msg)
         .allow_empty(false)
         .interact()?;
-    time::parse_time_span(&time_input)
+    Ok(time::parse_time_span_in(&time_input, calendar)?)
+}
+
+/// Like [``get_time_span_interactive``], but leaving the input empty gives `None`.
+fn get_optional_time_span_interactive(
+    msg: &str,
+    calendar: time::Calendar,
+) -> Result<Option<chrono::Duration>> {
+    let time_input = dialoguer::Input::<String>::new()
+        .with_prompt(msg)
+        .allow_empty(true)
+        .interact()?;
+    if time_input.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(time::parse_time_span_in(&time_input, calendar)?))
+}
+
+/// Have the user select a practice by fuzzy search. Practices are shown with their tags, which
+/// can be searched too.
+fn find_name(state: &State) -> Result<&str> {
+    let practices = state.practices().collect::<Vec<_>>();
+    let options = practices
+        .iter()
+        .map(|practice| {
+            let tags = practice.tags();
+            if tags.is_empty() {
+                practice.name().to_owned()
+            } else {
+                let tags = tags.iter().map(String::as_str).collect::<Vec<_>>();
+                format!("{}  [{}]", practice.name(), tags.join(", "))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let selection_index = dialoguer::FuzzySelect::new()
+        .with_prompt("Select practice")
+        .items(&options)
+        .interact_opt()
+        .context("Selection error.")?;
+
+    match selection_index {
+        Some(i) => Ok(practices[i].name()),
+        None => bail!("No item selected"),
+    }
+}
+
+/// Write content to file
+fn long_edit(intitial_content: Option<&str>) -> Result<String> {
+    dialoguer::Editor::new()
+        .require_save(false)
+        .edit(intitial_content.unwrap_or_default())?
+        .context("Content not saved")
 }
 
+/// Make sure `name` can be given to `prac session`, rather than being taken for one of its
+/// subcommands.
+fn ensure_not_session_command(name: &str) -> Result<()> {
+    ensure!(
+        !SessionCommand::has_subcommand(name) && name != "help",
+        "A practice can't be named \"{name}\", as `prac session {name}` is taken."
+    );
+    Ok(())
+}
+
+/// List journal entries about to be undone or redone, and ask whether to go ahead.
+fn confirm_revision(
+    verb: &str,
+    targets: &[journal::Entry],
+    calendar: time::Calendar,
+) -> Result<()> {
+    for entry in targets {
+        if let journal::Record::Transition(transition) = &entry.record {
+            let at = entry.at.with_timezone(&chrono::Local);
+            let transition = transition.display(calendar);
+            println!("  {}  {transition}", at.format("%Y-%m-%d %H:%M"));
+        }
+    }
+    let changes = if targets.len() == 1 {
+        "change"
+    } else {
+        "changes"
+    };
+    if !dialoguer::Confirm::new()
+        .with_prompt(format!("{verb} {} {changes}?", targets.len()))
+        .interact()?
+    {
+        bail!("aborted")
+    }
+    Ok(())
+}
+
+/// Interpret a `session` subcommand for sessions running in the background, returning the change
+/// (if any) it calls for.
+fn detached_session(state: &State, command: SessionCommand) -> Result<Option<Action>> {
+    let now = state.now();
+    let calendar = state.get_user_config().calendar;
+    let flat = |duration| time::FlatTime::in_calendar(duration, calendar);
+    match command {
+        SessionCommand::Start {
+            name,
+            max_time,
+            interactive,
+        } => {
+            if let Some(session) = state.active_session() {
+                bail!(
+                    "A session of \"{}\" is already running, stop it first.",
+                    session.name
+                );
+            }
+            let name = if interactive {
+                find_name(state)?.to_owned()
+            } else {
+                name.context("no practice name provided")?
+            };
+            let max_time = if interactive {
+                let msg = format!(
+                    "At most how long would you like to practice \"{name}?\" \
+                    (leave empty for the session cap)"
+                );
+                get_optional_time_span_interactive(&msg, calendar)?
+            } else {
+                max_time.map(|span| span.resolve(calendar)).transpose()?
+            }
+            .unwrap_or_else(|| state.get_user_config().session_cap);
+            let started = format!(
+                "Started \"{name}\". Run `prac session stop` when you're done, \
+                at most {} will be logged.",
+                flat(max_time).format()
+            );
+            let start = StateTransition::StartSession { name, max_time };
+            Ok(Some(Action::Apply(start, Some(started))))
+        }
+        SessionCommand::Stop { note } => {
+            let session = state.active_session().context("No session is running.")?;
+            let time = session.elapsed(now);
+            let logged = format!(
+                "Logged {} of \"{}\".",
+                flat(time).format_seconds(),
+                session.name
+            );
+            // a forgotten session ended when its max time ran out, not when it was stopped
+            let log = StateTransition::Log {
+                name: session.name.clone(),
+                time,
+                note,
+                pauses: Vec::new(),
+                ended: Some(session.start + time),
+            };
+            Ok(Some(Action::Stop {
+                started: session.start,
+                log,
+                done: logged,
+            }))
+        }
+        SessionCommand::Status => {
+            match state.active_session() {
+                Some(session) => println!(
+                    "\"{}\": {} elapsed of {}",
+                    session.name,
+                    flat(session.elapsed(now)).format_seconds(),
+                    flat(session.max_time).format_seconds()
+                ),
+                None => println!("No session is running."),
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// What a subcommand asks to be done to state.
+enum Action {
+    /// Apply the transition, then print what was done, if anything needs saying.
+    Apply(StateTransition, Option<String>),
+    /// Log the background session which started at `started`, provided it's still running.
+    Stop {
+        started: chrono::DateTime<chrono::Utc>,
+        log: StateTransition,
+        done: String,
+    },
+    /// Undo the transitions at these positions in the journal, most recent first.
+    Undo(Vec<u64>),
+    /// Redo the transitions at these positions in the journal, most recently undone first.
+    Redo(Vec<u64>),
+    Rebuild,
+}
+
+/// Interpret the subcommand, returning the change (if any) it calls for.
+///
+/// Nothing here changes state, as it may be changed by another prac in the meantime (say, while
+/// waiting on a prompt). Changes are instead applied to the latest state back in [``main``].
 #[allow(clippy::too_many_lines)]
-fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Path) -> Result<()> {
-    // TODO transition generation doesn't require &mut, this should be enforced somehow
+fn process_subcommand(
+    state: &State,
+    subcommand: SubCommand,
+    store: &Store,
+    style: BarStyle,
+) -> Result<Option<Action>> {
+    let calendar = state.get_user_config().calendar;
+    let resolve = |span: Option<cli::Span>| span.map(|span| span.resolve(calendar)).transpose();
     // TODO allow manual field specifications alongside interactive
     let transition = match subcommand {
         SubCommand::List {
             cumulative,
             period,
             danger,
+            format,
+            width,
+            sort,
+            reverse,
+            overdue,
+            due_within,
+            name,
+            regex,
+            tag,
+            flat,
         } => {
-            state.list(cumulative, period, danger)?;
-            return Ok(());
+            let mut listing = state.listing(state.now());
+            listing.filter(&listing::Filter {
+                overdue,
+                due_within: resolve(due_within)?,
+                name: name.or(regex),
+                tag,
+            });
+            listing.sort(sort, reverse);
+            match format {
+                Format::Bars => {
+                    let width = width.unwrap_or_else(utils::terminal_width);
+                    let columns = listing::Columns {
+                        cumulative,
+                        period,
+                        danger,
+                        grouped: !flat,
+                    };
+                    let theme = theme::enabled().then_some(&state.get_user_config().theme);
+                    print!("{}", listing.bars(columns, width, style, theme)?);
+                }
+                Format::Json => println!("{}", listing.json()?),
+                Format::Csv => print!("{}", listing.delimited(',', danger)),
+                Format::Tsv => print!("{}", listing.delimited('\t', danger)),
+            }
+            return Ok(None);
         }
         SubCommand::Add {
             name,
@@ -243,81 +494,121 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
             } else {
                 name.context("no practice name provided")?
             };
+            ensure_not_session_command(&name)?;
             let msg = format!("How often (not how long) would you like to practice \"{name}?\"");
             let period = if interactive {
-                get_time_span_interactive(&msg)?
+                get_time_span_interactive(&msg, calendar)?
             } else {
-                period.context("no period provided")?
+                resolve(period)?.context("no period provided")?
             };
             StateTransition::Add { name, period }
         }
         SubCommand::Log {
             name,
             time,
+            note,
+            at,
+            ago,
+            from,
+            to,
             interactive,
         } => {
             let name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 name.context("no practice name provided")?
             };
-            let msg = format!("How long did you practice \"{name}?\"");
-            let time = if interactive {
-                get_time_span_interactive(&msg)?
+            let now = state.now();
+            let instant = |input: Option<String>| {
+                input
+                    .map(|input| time::parse_datetime(&input, now, calendar))
+                    .transpose()
+            };
+            let from = instant(from)?;
+            let ended = if from.is_some() {
+                Some(instant(to)?.unwrap_or(now))
+            } else {
+                let ago = resolve(ago)?
+                    .map(|ago| {
+                        now.checked_sub_signed(ago)
+                            .context("Can't log from before there was time.")
+                    })
+                    .transpose()?;
+                ago.or(instant(at)?)
+            };
+            let time = if let Some(from) = from {
+                let to = ended.unwrap_or(now);
+                ensure!(from < to, "--from must be before when you finished.");
+                to - from
+            } else if interactive {
+                let msg = format!("How long did you practice \"{name}?\"");
+                get_time_span_interactive(&msg, calendar)?
             } else {
-                time.context("no time provided")?
+                resolve(time)?.context("no time provided")?
             };
-            StateTransition::Log { name, time }
+            StateTransition::Log {
+                name,
+                time,
+                note,
+                pauses: Vec::new(),
+                ended,
+            }
+        }
+        SubCommand::Session {
+            command: Some(command),
+            ..
+        } => {
+            return detached_session(state, command);
         }
         SubCommand::Session {
+            command: None,
             name,
             max_time,
+            no_clock,
             interactive,
         } => {
             let name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 name.context("no practice name provided")?
             };
 
             let max_time = if interactive {
-                let msg =
-                    format!("How long (not how often) would you like to practice \"{name}?\"");
-                get_time_span_interactive(&msg)?
+                let msg = format!(
+                    "How long (not how often) would you like to practice \"{name}?\" \
+                    (leave empty to practice until you finish)"
+                );
+                get_optional_time_span_interactive(&msg, calendar)?
             } else {
-                max_time.context("no time provided")?
+                resolve(max_time)?
             };
+            let limit = max_time.map_or_else(
+                || session::Limit::OpenEnded {
+                    cap: state.get_user_config().session_cap,
+                },
+                session::Limit::MaxTime,
+            );
 
-            // Print out how much time has passed untile ctrl-c is pressed.
-
-            let running = Arc::new(AtomicBool::new(true));
-            let r = running.clone();
-
-            ctrlc::set_handler(move || {
-                r.store(false, std::sync::atomic::Ordering::SeqCst);
-                println!("ctrl-c pressed");
-            })?;
-
-            let mut time = chrono::Duration::seconds(0);
-            let start = chrono::Utc::now();
-            while running.load(std::sync::atomic::Ordering::SeqCst) && time < max_time {
-                // TODO use bar, you already have it
-                print!(
-                    "\r{} elapsed of {}",
-                    time::FlatTime::from(time).format_seconds(),
-                    time::FlatTime::from(max_time).format_seconds()
-                );
-                std::io::stdout().flush()?;
-                std::thread::sleep(std::time::Duration::from_millis(1000));
-                time = chrono::Utc::now() - start;
+            let view = session::View {
+                period: state.period(&name)?,
+                name: name.clone(),
+                clock: !no_clock,
+                style,
+                calendar,
+            };
+            let outcome = session::run(
+                limit,
+                &view,
+                &state.get_user_config().notifications,
+                &*state.clock(),
+            )?;
+            StateTransition::Log {
+                name,
+                time: outcome.time,
+                note: outcome.note,
+                pauses: outcome.pauses,
+                ended: None,
             }
-
-            let time = (chrono::Utc::now() - start).min(max_time);
-
-            print!("\r{} elapsed", time::FlatTime::from(time).format_seconds());
-            std::io::stdout().flush()?;
-
-            StateTransition::Log { name, time }
         }
         SubCommand::Notes {
             name,
@@ -325,13 +616,13 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
             interactive,
         } => {
             let name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 name.context("no practice name provided")?
             };
             let notes = if interactive {
                 let old_notes = state.get_notes(&name)?;
-                utils::long_edit(Some(old_notes))?
+                long_edit(Some(old_notes))?
             } else {
                 new_notes.context("no notes provided")?
             };
@@ -339,8 +630,37 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
         }
         SubCommand::Reset => StateTransition::Reset,
         SubCommand::StateLocation => {
-            println!("{}", state_path.display());
-            return Ok(());
+            println!("{}", store.path().display());
+            return Ok(None);
+        }
+        SubCommand::Undo { count } => {
+            let targets = store.journal().undoable(count)?;
+            ensure!(!targets.is_empty(), "nothing to undo");
+            confirm_revision("Undo", &targets, calendar)?;
+            return Ok(Some(Action::Undo(
+                targets.iter().map(|entry| entry.seq).collect(),
+            )));
+        }
+        SubCommand::Redo { count } => {
+            let targets = store.journal().redoable(count)?;
+            ensure!(!targets.is_empty(), "nothing to redo");
+            confirm_revision("Redo", &targets, calendar)?;
+            return Ok(Some(Action::Redo(
+                targets.iter().map(|entry| entry.seq).collect(),
+            )));
+        }
+        SubCommand::Rebuild => {
+            if !dialoguer::Confirm::new()
+                .with_prompt(format!(
+                    "Replace \"{}\" with state rebuilt from \"{}\"?",
+                    store.path().display(),
+                    store.journal().path().display()
+                ))
+                .interact()?
+            {
+                bail!("aborted")
+            }
+            return Ok(Some(Action::Rebuild));
         }
         SubCommand::EditPeriod {
             name,
@@ -348,33 +668,95 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
             interactive,
         } => {
             let name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 name.context("no practice name provided")?
             };
             let msg = format!("How often (not how long) would you like to practice \"{name}?\"");
             let new_period = if interactive {
-                get_time_span_interactive(&msg)?
+                get_time_span_interactive(&msg, calendar)?
             } else {
-                period.context("no period provided")?
+                resolve(period)?.context("no period provided")?
             };
-            let display_period = time::FlatTime::from(new_period).format();
+            let display_period = time::FlatTime::in_calendar(new_period, calendar).format();
             if !dialoguer::Confirm::new()
-                .with_prompt(format!("Change period of \"{name}\" to {display_period}?",))
+                .with_prompt(format!("Change period of \"{name}\" to {display_period}?"))
                 .interact()?
             {
                 bail!("aborted")
             }
             StateTransition::EditPeriod { name, new_period }
         }
+        SubCommand::Tag {
+            name,
+            tag,
+            interactive,
+        } => {
+            let name = if interactive {
+                find_name(state)?.to_owned()
+            } else {
+                name.context("no practice name provided")?
+            };
+            let tag = if interactive {
+                dialoguer::Input::new()
+                    .with_prompt(format!("Tag \"{name}\" with"))
+                    .interact_text()?
+            } else {
+                tag.context("no tag provided")?
+            };
+            StateTransition::Tag { name, tag }
+        }
+        SubCommand::Weight {
+            name,
+            weight,
+            interactive,
+        } => {
+            let name = if interactive {
+                find_name(state)?.to_owned()
+            } else {
+                name.context("no practice name provided")?
+            };
+            let weight = if interactive {
+                dialoguer::Input::new()
+                    .with_prompt(format!("How important is \"{name}\" (1 is usual)?"))
+                    .interact_text()?
+            } else {
+                weight.context("no weight provided")?
+            };
+            StateTransition::Weight { name, weight }
+        }
+        SubCommand::Untag {
+            name,
+            tag,
+            interactive,
+        } => {
+            let name = if interactive {
+                find_name(state)?.to_owned()
+            } else {
+                name.context("no practice name provided")?
+            };
+            let tag = if interactive {
+                let tags = state.get_tags(&name)?;
+                ensure!(!tags.is_empty(), "\"{name}\" has no tags.");
+                let selection = dialoguer::FuzzySelect::new()
+                    .with_prompt("Select tag")
+                    .items(&tags)
+                    .interact_opt()?
+                    .context("No item selected")?;
+                tags[selection].clone()
+            } else {
+                tag.context("no tag provided")?
+            };
+            StateTransition::Untag { name, tag }
+        }
         SubCommand::Remove { name, interactive } => {
             let name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 name.context("no practice name provided")?
             };
             if !dialoguer::Confirm::new()
-                .with_prompt(format!("Remove practice \"{name}?\"",))
+                .with_prompt(format!("Remove practice \"{name}?\""))
                 .interact()?
             {
                 bail!("aborted")
@@ -387,7 +769,7 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
             interactive,
         } => {
             let current_name = if interactive {
-                state.find_name()?.to_owned()
+                find_name(state)?.to_owned()
             } else {
                 current_name.context("no current practice name provided")?
             };
@@ -399,6 +781,7 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
             } else {
                 new_name.context("no new practice name provided")?
             };
+            ensure_not_session_command(&new_name)?;
             StateTransition::Rename {
                 current_name,
                 new_name,
@@ -406,63 +789,197 @@ fn process_subcommand(state: &mut State, subcommand: SubCommand, state_path: &Pa
         }
         SubCommand::Config {
             grace_period,
+            session_cap,
+            bell,
+            notify_escape,
+            notify_hook,
+            theme,
+            danger,
+            calendar,
             interactive,
         } => {
-            let mut new_config = *state.get_user_config(); // TODO, this can't be right
-            if interactive {
-                // If interactive, we can either confirm on each non-provided field or "" for leave same
-                unimplemented!();
-            } else {
-                // else, only update provided fields
-                if let Some(grace_period) = grace_period {
-                    new_config.grace_period = grace_period;
-                }
+            // TODO interactive config, confirming each field not given or "" to leave it the same
+            ensure!(
+                !interactive,
+                "`prac config` can't be interactive yet, give the fields to change instead."
+            );
+            let mut new_config = state.get_user_config().clone();
+            // only update provided fields
+            if let Some(grace_period) = resolve(grace_period)? {
+                new_config.grace_period = grace_period;
+            }
+            if let Some(session_cap) = resolve(session_cap)? {
+                new_config.session_cap = session_cap;
+            }
+            if let Some(bell) = bell {
+                new_config.notifications.bell = bell;
+            }
+            if let Some(escape) = notify_escape {
+                new_config.notifications.escape = escape;
+            }
+            if let Some(hook) = notify_hook {
+                new_config.notifications.hook = (!hook.is_empty()).then_some(hook);
+            }
+            if let Some(theme) = theme {
+                new_config.theme = theme.theme();
+            }
+            if let Some(danger) = danger {
+                new_config.danger = danger;
+            }
+            if let Some(calendar) = calendar {
+                new_config.calendar = calendar;
             }
 
             StateTransition::Config { new_config }
         }
     };
 
-    handle_transition(state, transition)
+    Ok(Some(Action::Apply(transition, None)))
 }
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
-    let path = if let Some(path) = cli.path {
-        if !path.is_absolute() {
-            bail!("Path {} is not absolute", path.display())
-        } else {
+    let path = if let Some(path) = cli.path.take() {
+        if path.is_absolute() {
             path
+        } else {
+            bail!("Path {} is not absolute", path.display())
         }
     } else {
         State::get_path()?
     };
+    let store = Store::new(path).on_wait(|| {
+        eprintln!("Waiting for another prac to finish with the statefile...");
+    });
 
-    let mut state = if path.exists() {
-        serde_json::from_str(
-            &std::fs::read_to_string(&path).context("could not read statefile")?,
-        )
-        .with_context(|| format!("failed to parse state at \"{}\".\n\
-        Until automated state upgrading is implemented, you will either have to satisfy the parser's demands, or start with a new statefile. \
-        Be sure to save though.", path.display()))?
+    let (mut state, upgraded) = if matches!(cli.command, SubCommand::Rebuild) {
+        // whatever there is will be replaced
+        (State::new(), false)
     } else {
-        State::new()
+        let loaded = store.load()?;
+        if let Some((schema, backup)) = &loaded.upgraded_from {
+            eprintln!(
+                "Upgraded statefile from schema {schema} to {}. The original is kept at \"{}\".",
+                migration::SCHEMA_VERSION,
+                backup.display()
+            );
+        }
+        (loaded.state, loaded.upgraded_from.is_some())
     };
 
-    process_subcommand(&mut state, cli.command, &path)?;
+    // only the state shown is told the time, changes are always made by the system's clock
+    if let Some(clock) = preview_clock(&cli, state.get_user_config().calendar)? {
+        state.set_clock(clock);
+    }
 
-    if !path.parent().is_some_and(Path::exists) {
-        // create all subdirs
-        let parent = path.parent().context("state path has no parent")?;
-        if !parent.exists() {
-            std::fs::create_dir_all(parent).context("failed to create parent directories")?;
+    let style = if cli.ascii {
+        BarStyle::Ascii
+    } else {
+        BarStyle::Unicode
+    };
+    match process_subcommand(&state, cli.command, &store, style)? {
+        Some(Action::Apply(transition, done)) => {
+            store.update(|state, journal| journal.apply(state, transition))?;
+            if let Some(done) = done {
+                println!("{done}");
+            }
         }
+        Some(Action::Stop { started, log, done }) => {
+            store.update(|state, journal| {
+                // another prac may have stopped it (and started another) in the meantime
+                ensure!(
+                    state
+                        .active_session()
+                        .is_some_and(|session| session.start == started),
+                    "The session was stopped by another prac in the meantime."
+                );
+                journal.apply(state, log)
+            })?;
+            println!("{done}");
+        }
+        Some(Action::Undo(targets)) => store.update(|state, journal| {
+            ensure_unchanged(&journal.undoable(targets.len())?, &targets)?;
+            journal.undo(state, targets.len())
+        })?,
+        Some(Action::Redo(targets)) => store.update(|state, journal| {
+            ensure_unchanged(&journal.redoable(targets.len())?, &targets)?;
+            journal.redo(state, targets.len())
+        })?,
+        Some(Action::Rebuild) => store.rebuild()?,
+        None if upgraded => store.update(|_, _| Ok(()))?,
+        None => {}
     }
+    Ok(())
+}
+
+/// The clock `--now` asks for, if given. It's only for previewing, so commands which change state
+/// (and would record the made-up time in the journal) are refused.
+fn preview_clock(cli: &Cli, calendar: time::Calendar) -> Result<Option<Arc<dyn Clock>>> {
+    let Some(now) = &cli.now else {
+        return Ok(None);
+    };
+    ensure!(
+        matches!(
+            cli.command,
+            SubCommand::List { .. }
+                | SubCommand::StateLocation
+                | SubCommand::Session {
+                    command: Some(SessionCommand::Status),
+                    ..
+                }
+        ),
+        "--now is only for previewing, so it can't be used to change anything"
+    );
+    let now = time::parse_datetime(now, chrono::Utc::now(), calendar)?;
+    Ok(Some(Arc::new(FixedClock(now))))
+}
 
-    let state_file = std::fs::File::create(path).context("failed to create state file")?;
-    state.update_version();
-    serde_json::to_writer_pretty(BufWriter::new(state_file), &state)
-        .context("failed to write state to file")?;
+/// Make sure undo or redo would affect the transitions that were confirmed, which another prac
+/// could have changed in the meantime.
+fn ensure_unchanged(entries: &[journal::Entry], confirmed: &[u64]) -> Result<()> {
+    ensure!(
+        entries
+            .iter()
+            .map(|entry| entry.seq)
+            .eq(confirmed.iter().copied()),
+        "state was changed by another prac while confirming, try again"
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{preview_clock, process_subcommand};
+    use crate::cli::Cli;
+    use clap::Parser;
+    use prac::application::State;
+    use prac::store::Store;
+    use prac::time::Calendar;
+    use prac::utils::BarStyle;
+
+    #[test]
+    fn now_only_previews() {
+        let preview = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["prac", "--now", "2099-01-01 10:00"], args].concat())
+                .unwrap();
+            preview_clock(&cli, Calendar::default())
+        };
+        assert!(preview(&["list"]).unwrap().is_some());
+        assert!(preview(&["session", "status"]).unwrap().is_some());
+        assert!(preview(&["log", "steno", "30m"]).is_err());
+        assert!(preview(&["reset"]).is_err());
+        assert!(preview(&["session", "steno"]).is_err());
+    }
+
+    #[test]
+    fn logging_too_long_ago_fails() {
+        let cli = Cli::try_parse_from(["prac", "log", "steno", "30m", "--ago", "300000y"]).unwrap();
+        let store = Store::new(std::env::temp_dir().join("prac-unused.json"));
+        let result = process_subcommand(&State::new(), cli.command, &store, BarStyle::Unicode);
+        let error = result
+            .err()
+            .expect("should refuse to log from before time began");
+        assert!(error.to_string().contains("before there was time"));
+    }
+}
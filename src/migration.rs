@@ -0,0 +1,115 @@
+//! Upgrading statefiles written by older versions of prac.
+//!
+//! Each change to the layout of [``State``] bumps [``SCHEMA_VERSION``] and adds a migration to
+//! `MIGRATIONS`, which upgrades the raw json of the previous schema. Statefiles are then run
+//! through every migration from their own schema onwards before being parsed.
+
+use crate::application::State;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// Schema of statefiles written by this version of prac.
+pub const SCHEMA_VERSION: usize = 1;
+
+/// Upgrade from one schema to the next.
+type Migration = fn(&mut Value) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades schema `n` to `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION] = [record_completions];
+
+/// Parse a statefile of any schema up to [``SCHEMA_VERSION``], returning the schema it was
+/// written in alongside the upgraded state.
+pub fn load_state(text: &str) -> Result<(State, usize)> {
+    let mut value: Value = serde_json::from_str(text).context("statefile is not valid json")?;
+    let original = schema_version(&value)?;
+    if original > SCHEMA_VERSION {
+        bail!(
+            "statefile has schema {original}, but this version of prac only understands up to \
+            {SCHEMA_VERSION}. Try updating prac."
+        );
+    }
+
+    for (schema, migration) in MIGRATIONS.iter().enumerate().skip(original) {
+        migration(&mut value)
+            .with_context(|| format!("failed to upgrade statefile from schema {schema}"))?;
+        config_mut(&mut value)?.insert("schema".to_owned(), Value::from(schema + 1));
+    }
+
+    let state = serde_json::from_value(value)?;
+    Ok((state, original))
+}
+
+/// Statefiles from before schemas were tracked are schema 0.
+fn schema_version(value: &Value) -> Result<usize> {
+    let Some(schema) = value.get("config").and_then(|config| config.get("schema")) else {
+        return Ok(0);
+    };
+    schema
+        .as_u64()
+        .and_then(|schema| usize::try_from(schema).ok())
+        .context("statefile schema is not a number")
+}
+
+fn config_mut(value: &mut Value) -> Result<&mut serde_json::Map<String, Value>> {
+    value
+        .get_mut("config")
+        .and_then(Value::as_object_mut)
+        .context("statefile has no config")
+}
+
+/// 0 -> 1: Practices keep a list of completions rather than only the last log and a running
+/// total. The last log becomes the last reset, and the total is kept as untracked time.
+fn record_completions(value: &mut Value) -> Result<()> {
+    let practices = value
+        .get_mut("practices")
+        .and_then(Value::as_object_mut)
+        .context("statefile has no practices")?;
+    for (name, practice) in practices {
+        let practice = practice
+            .as_object_mut()
+            .with_context(|| format!("practice \"{name}\" is not an object"))?;
+        let logged = practice
+            .remove("logged")
+            .with_context(|| format!("practice \"{name}\" has no last log"))?;
+        practice.insert("reset".to_owned(), logged);
+        let cumulative = practice
+            .remove("cumulative")
+            .unwrap_or_else(|| Value::from(0));
+        practice.insert("untracked".to_owned(), cumulative);
+        practice.insert("completions".to_owned(), Value::Array(Vec::new()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_state, SCHEMA_VERSION};
+
+    #[test]
+    fn current_schema_is_unchanged() {
+        let text = serde_json::to_string(&crate::application::State::new()).unwrap();
+        let (state, schema) = load_state(&text).unwrap();
+        assert_eq!(schema, SCHEMA_VERSION);
+        assert_eq!(serde_json::to_string(&state).unwrap(), text);
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let text = format!(
+            r#"{{ "config": {{ "version": "99.0.0", "schema": {}, "user_config": {{}} }} }}"#,
+            SCHEMA_VERSION + 1
+        );
+        assert!(load_state(&text).is_err());
+    }
+
+    #[test]
+    fn malformed_legacy_practice_is_reported() {
+        let text = r#"{
+            "config": { "version": "0.1.6", "user_config": { "grace_period_in_seconds": 0 } },
+            "practices": { "steno": { "name": "steno" } }
+        }"#;
+        let error = format!("{:#}", load_state(text).err().unwrap());
+        assert!(error.contains("from schema 0"), "{error}");
+        assert!(error.contains("\"steno\" has no last log"), "{error}");
+    }
+}
@@ -0,0 +1,142 @@
+//! Letting you know a session is up, in whichever ways are configured in
+//! [``UserConfig``](crate::application::UserConfig).
+//!
+//! The bell and escape sequences are written to the terminal, and understood (or ignored) by it.
+//! The hook is any shell command, e.g. `notify-send "$PRAC_MESSAGE"`. None are on by default, so a
+//! session is as quiet as it always was until you choose otherwise.
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Terminal escape sequence to request a desktop notification with.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Escape {
+    /// Don't send one.
+    Off,
+    /// OSC 9, as understood by iTerm2, Windows Terminal, kitty and others.
+    Osc9,
+    /// OSC 777, as understood by urxvt, foot, ghostty and others.
+    Osc777,
+}
+
+/// How to notify when a session's time is up.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Notifications {
+    /// Ring the terminal bell.
+    pub bell: bool,
+    pub escape: Escape,
+    /// Shell command to run, with `PRAC_PRACTICE` and `PRAC_MESSAGE` set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hook: Option<String>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            bell: false,
+            escape: Escape::Off,
+            hook: None,
+        }
+    }
+}
+
+impl Notifications {
+    /// Notify that the named practice's session is up, writing any bell or escape to `terminal`.
+    pub fn send(&self, terminal: &mut impl Write, practice: &str, message: &str) -> Result<()> {
+        if self.bell {
+            write!(terminal, "\x07")?;
+        }
+        // control characters (say, in a practice's name) could end the sequence and start another
+        let message = message
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect::<String>();
+        match self.escape {
+            Escape::Off => {}
+            Escape::Osc9 => write!(terminal, "\x1b]9;{message}\x07")?,
+            Escape::Osc777 => write!(terminal, "\x1b]777;notify;prac;{message}\x07")?,
+        }
+        terminal.flush()?;
+
+        if let Some(hook) = &self.hook {
+            let status = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(hook)
+                .env("PRAC_PRACTICE", practice)
+                .env("PRAC_MESSAGE", message)
+                .status()
+                .context("failed to run notification hook")?;
+            ensure!(status.success(), "notification hook failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Escape, Notifications};
+    use crate::testing::TempDir;
+
+    #[test]
+    fn terminal_notifications_follow_config() {
+        let mut terminal = Vec::new();
+        Notifications::default()
+            .send(&mut terminal, "steno", "up")
+            .unwrap();
+        assert!(terminal.is_empty());
+
+        let mut notifications = Notifications {
+            bell: true,
+            escape: Escape::Osc9,
+            hook: None,
+        };
+        let mut terminal = Vec::new();
+        notifications.send(&mut terminal, "steno", "up").unwrap();
+        assert_eq!(terminal, b"\x07\x1b]9;up\x07");
+
+        let mut terminal = Vec::new();
+        notifications
+            .send(&mut terminal, "steno", "\"st\x07\x1b]9;eno\" is up")
+            .unwrap();
+        assert_eq!(terminal, b"\x07\x1b]9;\"st]9;eno\" is up\x07");
+
+        notifications.bell = false;
+        notifications.escape = Escape::Osc777;
+        let mut terminal = Vec::new();
+        notifications.send(&mut terminal, "steno", "up").unwrap();
+        assert_eq!(terminal, b"\x1b]777;notify;prac;up\x07");
+
+        notifications.escape = Escape::Off;
+        let mut terminal = Vec::new();
+        notifications.send(&mut terminal, "steno", "up").unwrap();
+        assert!(terminal.is_empty());
+    }
+
+    #[test]
+    fn hook_runs_with_practice_and_message() {
+        let dir = TempDir::new("hook");
+        let out = dir.path().join("out");
+
+        let notifications = Notifications {
+            bell: false,
+            escape: Escape::Off,
+            hook: Some(format!(
+                "echo \"$PRAC_PRACTICE: $PRAC_MESSAGE\" > '{}'",
+                out.display()
+            )),
+        };
+        notifications
+            .send(&mut std::io::sink(), "steno", "time's up")
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "steno: time's up\n");
+
+        let failing = Notifications {
+            hook: Some("exit 3".to_owned()),
+            ..notifications
+        };
+        assert!(failing.send(&mut std::io::sink(), "steno", "up").is_err());
+    }
+}
@@ -0,0 +1,422 @@
+//! Sessions timed in the terminal, which can be paused, noted, and finished early from the
+//! keyboard.
+//!
+//! A running session is drawn as two bars the width of the terminal: the session's progress
+//! towards its max time, and the practice's progress through its period as in `prac list`, which
+//! empties when the session is logged. Open-ended sessions have no max time, so only the practice's
+//! bar moves.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use prac::application::{Pause, Period};
+use prac::clock::Clock;
+use prac::notify::Notifications;
+use prac::time::{Calendar, FlatTime};
+use prac::utils::{terminal_width, BarStyle};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+
+/// How often to check for keys and redraw.
+const TICK: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Keeps time for a session, not counting any time spent paused.
+pub struct Timer {
+    start: DateTime<Utc>,
+    pauses: Vec<Pause>,
+    paused_since: Option<DateTime<Utc>>,
+}
+
+impl Timer {
+    pub const fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            pauses: Vec::new(),
+            paused_since: None,
+        }
+    }
+
+    pub const fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
+
+    /// Pause if running, or resume if paused.
+    pub fn toggle(&mut self, now: DateTime<Utc>) {
+        match self.paused_since.take() {
+            Some(start) => self.pauses.push(Pause { start, end: now }),
+            None => self.paused_since = Some(now),
+        }
+    }
+
+    /// Time spent not paused.
+    pub fn active(&self, now: DateTime<Utc>) -> Duration {
+        let paused = self
+            .pauses
+            .iter()
+            .fold(Duration::zero(), |sum, pause| sum + pause.duration());
+        let pausing = self
+            .paused_since
+            .map_or_else(Duration::zero, |since| now - since);
+        now - self.start - paused - pausing
+    }
+
+    /// Stop the timer (resuming if paused), returning its pauses.
+    pub fn finish(mut self, now: DateTime<Utc>) -> Vec<Pause> {
+        if self.is_paused() {
+            self.toggle(now);
+        }
+        self.pauses
+    }
+}
+
+/// How a session went, ready to be logged.
+pub struct Outcome {
+    /// Active time, at most the session's max time.
+    pub time: Duration,
+    pub pauses: Vec<Pause>,
+    pub note: Option<String>,
+}
+
+/// When a session ends without being finished.
+#[derive(Clone, Copy)]
+pub enum Limit {
+    /// Once this much active time has passed.
+    MaxTime(Duration),
+    /// Not until finished, except as a safety net once `cap` has passed.
+    OpenEnded { cap: Duration },
+}
+
+impl Limit {
+    const fn time(self) -> Duration {
+        match self {
+            Self::MaxTime(time) | Self::OpenEnded { cap: time } => time,
+        }
+    }
+}
+
+/// How to draw a running session.
+pub struct View {
+    /// Practice the session is for.
+    pub name: String,
+    /// The practice's period, drawn alongside the session.
+    pub period: Period,
+    /// Whether to show times, or only bars.
+    pub clock: bool,
+    pub style: BarStyle,
+    /// How long months and years are in the times shown.
+    pub calendar: Calendar,
+}
+
+impl View {
+    /// Lines for the session and period bars, each `width` columns wide. `period_fraction` is
+    /// passed separately so the period can be drawn reset at the end of the session.
+    fn bars(
+        &self,
+        width: usize,
+        active: Duration,
+        limit: Limit,
+        period_fraction: f64,
+    ) -> [String; 2] {
+        let labels = ["session", self.name.as_str()];
+        let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 3;
+
+        let clock = if self.clock {
+            // Reserve room for the longest the elapsed time gets, so the bar doesn't jitter.
+            let elapsed_width = [limit.time() - Duration::seconds(1), limit.time()]
+                .into_iter()
+                .map(|time| {
+                    FlatTime::in_calendar(time, self.calendar)
+                        .format_seconds()
+                        .len()
+                })
+                .max()
+                .unwrap_or(0);
+            let elapsed = FlatTime::in_calendar(active, self.calendar).format_seconds();
+            match limit {
+                Limit::MaxTime(max_time) => format!(
+                    " {elapsed:>elapsed_width$} of {}  ",
+                    FlatTime::in_calendar(max_time, self.calendar).format_seconds()
+                ),
+                Limit::OpenEnded { .. } => format!(" {elapsed:>elapsed_width$}  "),
+            }
+        } else {
+            "  ".to_owned()
+        };
+        let bar_width = width.saturating_sub(label_width + clock.len());
+
+        #[allow(clippy::cast_precision_loss)]
+        let session_fraction = match limit {
+            Limit::MaxTime(max_time) => active.num_seconds() as f64 / max_time.num_seconds() as f64,
+            Limit::OpenEnded { .. } => 0.0,
+        };
+        let ends = [clock.as_str(), ""];
+        let fractions = [session_fraction, period_fraction];
+        let mut lines = itertools::izip!(labels, fractions, ends).map(|(label, fraction, end)| {
+            format!(
+                "{:>label_width$}{}{end:<clock_width$}",
+                format!("  {label} "),
+                prac::utils::bar(bar_width, fraction, self.style),
+                clock_width = clock.len()
+            )
+        });
+        [
+            lines.next().unwrap_or_default(),
+            lines.next().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Time a session by `clock` until its limit or until it is finished early, notifying if the
+/// limit is reached.
+///
+/// With a terminal, space pauses and resumes, `a` adds a note, and enter (or ctrl-c) finishes.
+/// Otherwise, only ctrl-c is available to finish early.
+pub fn run(
+    limit: Limit,
+    view: &View,
+    notifications: &Notifications,
+    clock: &dyn Clock,
+) -> Result<Outcome> {
+    let outcome = if termion::is_tty(&std::io::stdin()) {
+        run_with_keys(limit, view, clock)?
+    } else {
+        run_until_interrupted(limit, view, clock)?
+    };
+    if outcome.time >= limit.time() {
+        let message = match limit {
+            Limit::MaxTime(_) => format!("Time's up for \"{}\"", view.name),
+            Limit::OpenEnded { .. } => format!("\"{}\" reached the session cap", view.name),
+        };
+        let mut stdout = std::io::stdout();
+        let sent = if termion::is_tty(&stdout) {
+            notifications.send(&mut stdout, &view.name, &message)
+        } else {
+            notifications.send(&mut std::io::sink(), &view.name, &message)
+        };
+        if let Err(error) = sent {
+            eprintln!("Couldn't notify: {error:#}");
+        }
+    }
+    if view.clock {
+        println!(
+            "{} elapsed",
+            FlatTime::in_calendar(outcome.time, view.calendar).format_seconds()
+        );
+    }
+    if let Limit::OpenEnded { cap } = limit {
+        if outcome.time >= cap {
+            println!(
+                "Stopped at the session cap of {}, which `prac config --session-cap` can change.",
+                FlatTime::in_calendar(cap, view.calendar).format()
+            );
+        }
+    }
+    Ok(outcome)
+}
+
+/// A note being written, and then those already written.
+#[derive(Default)]
+struct Notes {
+    draft: Option<String>,
+    done: Vec<String>,
+}
+
+/// Draw the session bars and a status line, over the previous drawing if there is one.
+fn draw(
+    out: &mut impl Write,
+    redraw: bool,
+    [session, period]: &[String; 2],
+    status: &str,
+) -> Result<()> {
+    if redraw {
+        write!(out, "{}", termion::cursor::Up(2))?;
+    }
+    let clear = termion::clear::CurrentLine;
+    write!(
+        out,
+        "\r{clear}{session}\r\n{clear}{period}\r\n{clear}{status}"
+    )?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run_with_keys(limit: Limit, view: &View, clock: &dyn Clock) -> Result<Outcome> {
+    let mut stdout = std::io::stdout().into_raw_mode()?;
+    let mut keys = termion::async_stdin().keys();
+    let mut timer = Timer::new(clock.now());
+    let mut notes = Notes::default();
+
+    write!(
+        stdout,
+        "space: pause/resume, a: add note, enter: finish\r\n"
+    )?;
+    let mut redraw = false;
+    'session: while timer.active(clock.now()) < limit.time() {
+        for key in keys.by_ref() {
+            let key = key?;
+            if let Some(draft) = &mut notes.draft {
+                match key {
+                    Key::Char('\n') => {
+                        let note = std::mem::take(draft);
+                        notes.draft = None;
+                        if !note.trim().is_empty() {
+                            notes.done.push(note);
+                        }
+                    }
+                    Key::Char(c) => draft.push(c),
+                    Key::Backspace => {
+                        draft.pop();
+                    }
+                    Key::Esc => notes.draft = None,
+                    Key::Ctrl('c') => break 'session,
+                    _ => {}
+                }
+                continue;
+            }
+            match key {
+                Key::Char(' ') => timer.toggle(clock.now()),
+                Key::Char('a') => notes.draft = Some(String::new()),
+                Key::Char('\n') | Key::Ctrl('c') => break 'session,
+                _ => {}
+            }
+        }
+
+        let now = clock.now();
+        let status = notes.draft.as_ref().map_or_else(
+            || if timer.is_paused() { "paused" } else { "" }.to_owned(),
+            |draft| format!("note: {draft}_"),
+        );
+        let bars = view.bars(
+            terminal_width(),
+            timer.active(now),
+            limit,
+            view.period.fraction(now),
+        );
+        draw(&mut stdout, redraw, &bars, &status)?;
+        redraw = true;
+        std::thread::sleep(TICK);
+    }
+
+    let now = clock.now();
+    let time = timer.active(now).min(limit.time());
+    let bars = view.bars(terminal_width(), time, limit, 0.0);
+    draw(&mut stdout, redraw, &bars, "")?;
+    write!(stdout, "\r\n")?;
+
+    let note = (!notes.done.is_empty()).then(|| notes.done.join("\n"));
+    Ok(Outcome {
+        time,
+        pauses: timer.finish(now),
+        note,
+    })
+}
+
+/// Without a terminal to read keys from, run until ctrl-c.
+fn run_until_interrupted(limit: Limit, view: &View, clock: &dyn Clock) -> Result<Outcome> {
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })?;
+
+    let timer = Timer::new(clock.now());
+    let mut stdout = std::io::stdout();
+    let mut redraw = false;
+    while running.load(Ordering::SeqCst) && timer.active(clock.now()) < limit.time() {
+        let now = clock.now();
+        let bars = view.bars(
+            terminal_width(),
+            timer.active(now),
+            limit,
+            view.period.fraction(now),
+        );
+        draw(&mut stdout, redraw, &bars, "")?;
+        redraw = true;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+
+    let now = clock.now();
+    let time = timer.active(now).min(limit.time());
+    let bars = view.bars(terminal_width(), time, limit, 0.0);
+    draw(&mut stdout, redraw, &bars, "")?;
+    println!();
+    Ok(Outcome {
+        time,
+        pauses: timer.finish(now),
+        note: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calendar, Limit, Timer, View};
+    use chrono::{Duration, Utc};
+    use prac::application::{Pause, Period};
+    use prac::utils::BarStyle;
+
+    #[test]
+    fn paused_time_is_not_active() {
+        let start = Utc::now();
+        let minutes = |m| start + Duration::minutes(m);
+        let mut timer = Timer::new(start);
+
+        timer.toggle(minutes(10));
+        assert!(timer.is_paused());
+        assert_eq!(timer.active(minutes(25)), Duration::minutes(10));
+        timer.toggle(minutes(30));
+        assert_eq!(timer.active(minutes(45)), Duration::minutes(25));
+        timer.toggle(minutes(50));
+
+        assert_eq!(
+            timer.finish(minutes(55)),
+            [
+                Pause {
+                    start: minutes(10),
+                    end: minutes(30)
+                },
+                Pause {
+                    start: minutes(50),
+                    end: minutes(55)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn bars_fill_width_and_hide_clock() {
+        let mut view = View {
+            name: "steno".to_owned(),
+            period: Period {
+                since: Utc::now(),
+                length: Duration::days(1),
+            },
+            clock: true,
+            style: BarStyle::Unicode,
+            calendar: Calendar::default(),
+        };
+        let limit = Limit::MaxTime(Duration::minutes(30));
+        let bars = view.bars(60, Duration::minutes(15), limit, 0.25);
+        assert!(bars[0].contains("15m of 30m"), "{}", bars[0]);
+        for bar in &bars {
+            assert_eq!(bar.chars().count(), 60, "{bar}");
+        }
+
+        let open_ended = Limit::OpenEnded {
+            cap: Duration::hours(4),
+        };
+        let bars = view.bars(60, Duration::minutes(15), open_ended, 0.25);
+        assert!(bars[0].ends_with(" 15m  "), "{}", bars[0]);
+        assert!(!bars[0].contains('\u{025AC}'), "{}", bars[0]);
+
+        view.clock = false;
+        let bars = view.bars(60, Duration::minutes(15), limit, 0.25);
+        for bar in &bars {
+            assert_eq!(bar.chars().count(), 60, "{bar}");
+            assert!(!bar.chars().any(|c| c.is_ascii_digit()), "{bar}");
+        }
+    }
+}
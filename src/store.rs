@@ -0,0 +1,306 @@
+//! Reading and writing the statefile, safely across crashes and concurrent prac processes.
+//!
+//! Every write goes to a temporary file which is synced and then renamed over the statefile, so
+//! the statefile is always either entirely old or entirely new. Access is coordinated with an
+//! advisory lock on a file next to the statefile. Changes are made by re-reading the latest state
+//! under an exclusive lock and applying them on top, so that e.g. a `log` in one terminal isn't
+//! lost to a long-running `session` in another.
+
+use crate::application::State;
+use crate::clock::{Clock, SystemClock};
+use crate::journal::Journal;
+use crate::migration::{load_state, SCHEMA_VERSION};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Number of previous statefiles kept, as `<statefile>.1` (most recent) to `<statefile>.N`.
+pub const BACKUP_COUNT: usize = 3;
+
+/// A statefile, as it was read.
+pub struct Loaded {
+    pub state: State,
+    /// If the statefile was written in an older schema, that schema and where the original was
+    /// backed up to before upgrading.
+    pub upgraded_from: Option<(usize, PathBuf)>,
+}
+
+/// The statefile at a given path, along with its journal.
+pub struct Store {
+    path: PathBuf,
+    journal: Journal,
+    /// Called when another process has the statefile locked, before waiting on it.
+    on_wait: Option<Box<dyn Fn()>>,
+    /// Set on every state read, see [``Store::clock``].
+    clock: Arc<dyn Clock>,
+}
+
+impl Store {
+    pub fn new(path: PathBuf) -> Self {
+        let journal = Journal::new(&path);
+        Self {
+            path,
+            journal,
+            on_wait: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Have states read from this store, and changes made to them, tell the time by `clock`.
+    #[must_use]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Call `notice` whenever another process has the statefile locked, e.g. to say why nothing
+    /// is happening.
+    #[must_use]
+    pub fn on_wait(mut self, notice: impl Fn() + 'static) -> Self {
+        self.on_wait = Some(Box::new(notice));
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub const fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Read the current state, waiting on any process in the middle of changing it.
+    pub fn load(&self) -> Result<Loaded> {
+        let mut loaded = {
+            let _lock = self.lock(false)?;
+            self.read(false)?
+        };
+        if loaded
+            .upgraded_from
+            .as_ref()
+            .is_some_and(|(_, backup)| !backup.exists())
+        {
+            let _lock = self.lock(true)?;
+            loaded = self.read(true)?;
+        }
+        loaded.state.set_clock(self.clock.clone());
+        Ok(loaded)
+    }
+
+    /// Apply `change` to the latest state and write the result, while no other process can.
+    pub fn update<T>(&self, change: impl FnOnce(&mut State, &Journal) -> Result<T>) -> Result<T> {
+        let _lock = self.lock(true)?;
+        let mut state = self.read(true)?.state;
+        state.set_clock(self.clock.clone());
+        let result = change(&mut state, &self.journal)?;
+        self.write(&mut state)?;
+        Ok(result)
+    }
+
+    /// Replace the statefile with one rebuilt from the journal, without reading it.
+    pub fn rebuild(&self) -> Result<()> {
+        let _lock = self.lock(true)?;
+        let mut state = self.journal.rebuild()?;
+        self.write(&mut state)
+    }
+
+    /// Read state, bringing it up to date with the current schema and the journal.
+    ///
+    /// A statefile in an older schema is only backed up if the lock held is `exclusive`, so that no
+    /// two prac write the backup at once.
+    fn read(&self, exclusive: bool) -> Result<Loaded> {
+        if !self.path.exists() {
+            // a journal starts from a checkpoint of the state before it, which may well have had
+            // practices in it
+            let state = if self.journal.path().exists() {
+                self.journal.rebuild().with_context(|| {
+                    format!(
+                        "statefile \"{}\" is missing, and couldn't be rebuilt from the journal",
+                        self.path.display()
+                    )
+                })?
+            } else {
+                State::new()
+            };
+            return Ok(Loaded {
+                state,
+                upgraded_from: None,
+            });
+        }
+
+        let text = std::fs::read_to_string(&self.path).context("could not read statefile")?;
+        let (mut state, schema) = load_state(&text).with_context(|| {
+            format!(
+                "failed to parse state at \"{}\".\n\
+                If the statefile is corrupt, `prac rebuild` can restore it from the journal.",
+                self.path.display()
+            )
+        })?;
+        let upgraded_from = if schema < SCHEMA_VERSION {
+            let backup = self.path.with_extension(format!("schema-{schema}.json"));
+            if exclusive && !backup.exists() {
+                write_atomically(&backup, text.as_bytes())
+                    .context("failed to back up statefile")?;
+            }
+            Some((schema, backup))
+        } else {
+            None
+        };
+        self.journal.catch_up(&mut state)?;
+        Ok(Loaded {
+            state,
+            upgraded_from,
+        })
+    }
+
+    /// Write state, keeping the last few statefiles as backups.
+    fn write(&self, state: &mut State) -> Result<()> {
+        state.update_version();
+        let text = serde_json::to_vec_pretty(state).context("failed to serialize state")?;
+
+        if self.path.exists() {
+            for n in (1..BACKUP_COUNT).rev() {
+                let from = self.backup_path(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.backup_path(n + 1))
+                        .context("failed to rotate backups")?;
+                }
+            }
+            if BACKUP_COUNT > 0 {
+                std::fs::copy(&self.path, self.backup_path(1))
+                    .context("failed to back up statefile")?;
+            }
+        }
+
+        write_atomically(&self.path, &text).context("failed to write state to file")
+    }
+
+    fn backup_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_owned();
+        name.push(format!(".{n}"));
+        self.path.with_file_name(name)
+    }
+
+    /// Take the advisory lock, shared for reading or exclusive for writing. Released on drop.
+    fn lock(&self, exclusive: bool) -> Result<File> {
+        let parent = self.path.parent().context("state path has no parent")?;
+        std::fs::create_dir_all(parent).context("failed to create parent directories")?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("lock"))
+            .context("failed to open lockfile")?;
+
+        let acquired = if exclusive {
+            file.try_lock()
+        } else {
+            file.try_lock_shared()
+        };
+        match acquired {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                if let Some(notice) = &self.on_wait {
+                    notice();
+                }
+                if exclusive {
+                    file.lock()
+                } else {
+                    file.lock_shared()
+                }
+                .context("failed to lock statefile")?;
+            }
+            Err(std::fs::TryLockError::Error(error)) => {
+                return Err(error).context("failed to lock statefile")
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Write a file such that it is never seen partially written, even if interrupted.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp_name = path
+        .file_name()
+        .context("path has no file name")?
+        .to_owned();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = File::create(&temp_path).context("failed to create temporary file")?;
+    file.write_all(contents)
+        .context("failed to write temporary file")?;
+    file.sync_all().context("failed to sync temporary file")?;
+    std::fs::rename(&temp_path, path).context("failed to replace file")?;
+
+    // Make the rename itself durable. Not every platform can open directories, which is fine.
+    if let Some(Ok(dir)) = path.parent().map(File::open) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Store, BACKUP_COUNT};
+    use crate::application::handle_transition;
+    use crate::testing::{add, TempDir};
+
+    #[test]
+    fn updates_apply_to_latest_state() {
+        let dir = TempDir::new("merge");
+        let store = Store::new(dir.state_path());
+        store
+            .update(|state, journal| journal.apply(state, add("steno")))
+            .unwrap();
+        store
+            .update(|state, journal| journal.apply(state, add("kierkegaard")))
+            .unwrap();
+
+        let state = store.load().unwrap().state;
+        assert_eq!(state.journal_seq(), 2);
+        assert!(state.get_notes("steno").is_ok());
+        assert!(state.get_notes("kierkegaard").is_ok());
+    }
+
+    #[test]
+    fn missing_statefile_is_rebuilt_from_checkpoint() {
+        let dir = TempDir::new("missing");
+        let store = Store::new(dir.state_path());
+        // from before journaling began
+        store
+            .update(|state, _| handle_transition(state, add("steno"), state.now()))
+            .unwrap();
+        store
+            .update(|state, journal| journal.apply(state, add("piano")))
+            .unwrap();
+
+        std::fs::remove_file(store.path()).unwrap();
+        let state = store.load().unwrap().state;
+        assert!(state.get_notes("steno").is_ok());
+        assert!(state.get_notes("piano").is_ok());
+        assert_eq!(state.journal_seq(), 1);
+    }
+
+    #[test]
+    fn backups_rotate() {
+        let dir = TempDir::new("backups");
+        let store = Store::new(dir.state_path());
+        for n in 0..=BACKUP_COUNT + 1 {
+            store
+                .update(|state, journal| journal.apply(state, add(&n.to_string())))
+                .unwrap();
+        }
+        assert!(store.backup_path(BACKUP_COUNT).exists());
+        assert!(!store.backup_path(BACKUP_COUNT + 1).exists());
+
+        let text = std::fs::read_to_string(store.backup_path(1)).unwrap();
+        let (previous, _) = crate::migration::load_state(&text).unwrap();
+        assert_eq!(
+            previous.journal_seq(),
+            store.load().unwrap().state.journal_seq() - 1
+        );
+    }
+}
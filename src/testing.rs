@@ -0,0 +1,52 @@
+//! Fixtures shared between tests.
+
+use crate::application::StateTransition;
+use chrono::Duration;
+use std::path::{Path, PathBuf};
+
+/// A directory of a test's own, removed along with everything in it when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// A fresh directory for the named test, which must be unique across tests.
+    pub fn new(test: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("prac-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("could not create test directory");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Where a statefile in this directory would go.
+    pub fn state_path(&self) -> PathBuf {
+        self.0.join("prac.json")
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Add a practice with a period of a day.
+pub fn add(name: &str) -> StateTransition {
+    StateTransition::Add {
+        name: name.to_owned(),
+        period: Duration::days(1),
+    }
+}
+
+/// Log minutes of a practice, ending now.
+pub fn log(name: &str, minutes: i64) -> StateTransition {
+    StateTransition::Log {
+        name: name.to_owned(),
+        time: Duration::minutes(minutes),
+        note: None,
+        pauses: Vec::new(),
+        ended: None,
+    }
+}
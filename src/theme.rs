@@ -0,0 +1,144 @@
+//! Colors for bars, by how urgent the practice they show is.
+
+use serde::{Deserialize, Serialize};
+
+/// A terminal color.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    /// Whatever the terminal draws text in.
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl Color {
+    /// SGR foreground code, if any.
+    const fn code(self) -> Option<u8> {
+        match self {
+            Self::Default => None,
+            Self::Black => Some(30),
+            Self::Red => Some(31),
+            Self::Green => Some(32),
+            Self::Yellow => Some(33),
+            Self::Blue => Some(34),
+            Self::Magenta => Some(35),
+            Self::Cyan => Some(36),
+            Self::White => Some(37),
+            Self::BrightBlack => Some(90),
+            Self::BrightRed => Some(91),
+            Self::BrightGreen => Some(92),
+            Self::BrightYellow => Some(93),
+            Self::BrightBlue => Some(94),
+            Self::BrightMagenta => Some(95),
+            Self::BrightCyan => Some(96),
+            Self::BrightWhite => Some(97),
+        }
+    }
+
+    pub fn paint(self, text: &str) -> String {
+        match self.code() {
+            Some(code) if !text.is_empty() => format!("\x1b[{code}m{text}\x1b[39m"),
+            _ => text.to_owned(),
+        }
+    }
+}
+
+/// Colors for bars through their period, then past it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Theme {
+    /// Bars with plenty of period left.
+    pub fresh: Color,
+    /// Bars past [``Theme::due_from``] of their period.
+    pub due: Color,
+    /// Bars past their period.
+    pub overdue: Color,
+    /// Fraction of the period from which a bar is due.
+    pub due_from: f64,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Preset::Traffic.theme()
+    }
+}
+
+impl Theme {
+    /// Color of a bar `fraction` of the way through its period.
+    pub fn color(&self, fraction: f64) -> Color {
+        if fraction >= 1.0 {
+            self.overdue
+        } else if fraction >= self.due_from {
+            self.due
+        } else {
+            self.fresh
+        }
+    }
+}
+
+/// Themes to choose from with `prac config --theme`. Others can be set in the statefile.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Preset {
+    /// Green, then yellow, then red.
+    Traffic,
+    /// Blue, then cyan, then magenta, for those who can't tell red from green.
+    Ocean,
+    /// No colors.
+    Mono,
+}
+
+impl Preset {
+    pub const fn theme(self) -> Theme {
+        let (fresh, due, overdue) = match self {
+            Self::Traffic => (Color::Green, Color::Yellow, Color::Red),
+            Self::Ocean => (Color::Blue, Color::Cyan, Color::Magenta),
+            Self::Mono => (Color::Default, Color::Default, Color::Default),
+        };
+        Theme {
+            fresh,
+            due,
+            overdue,
+            due_from: 0.75,
+        }
+    }
+}
+
+/// Whether to color output: only to a terminal, and not if `NO_COLOR` is set (see
+/// <https://no-color.org>).
+pub fn enabled() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && termion::is_tty(&std::io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Preset};
+
+    #[test]
+    fn colors_follow_urgency() {
+        let theme = Preset::Traffic.theme();
+        assert_eq!(theme.color(0.2), Color::Green);
+        assert_eq!(theme.color(0.75), Color::Yellow);
+        assert_eq!(theme.color(1.0), Color::Red);
+        assert_eq!(theme.color(3.0), Color::Red);
+
+        assert_eq!(Color::Red.paint("==="), "\x1b[31m===\x1b[39m");
+        assert_eq!(Color::Default.paint("==="), "===");
+        assert_eq!(Color::Red.paint(""), "");
+    }
+}
@@ -1,68 +1,419 @@
-use anyhow::{bail, Result};
 use chrono::Duration;
+use pest::error::InputLocation;
+use pest::iterators::Pair;
 use pest::Parser;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Write};
 
-/// Parser for an approximate superset of [systemd.time](https://www.freedesktop.org/software/systemd/man/systemd.time.html#:~:text=Internally%2C%20systemd%20generally%20operates%20with,usually%20seconds%20(see%20above)).
-/// Exceptions, also contains ns. Year is 365 days not 365.25, and month is 30 days not 30.44.
+mod datetime;
+
+pub use datetime::{parse_datetime, parse_datetime_in, DateTimeError};
+
+/// Parser for an approximate superset of [systemd.time].
+///
+/// Exceptions, also contains ns, decimal quantities (`1.5h`) and clock-style spans (`1:30:00`).
+/// Months and years are as long as the [``Calendar``] says.
+///
+/// [systemd.time]: https://www.freedesktop.org/software/systemd/man/systemd.time.html#:~:text=Internally%2C%20systemd%20generally%20operates%20with,usually%20seconds%20(see%20above)
 #[derive(pest_derive::Parser)]
 #[grammar = "time/time.pest"]
 pub struct SystemDStyleTimeParser;
 
+/// How long months and years are, when parsing and displaying time spans.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Calendar {
+    /// Months of 30 days and years of 365, as prac always had.
+    #[default]
+    Approximate,
+    /// Months of 30.44 days and years of 365.25, as in systemd.time.
+    Systemd,
+}
+
+impl Calendar {
+    /// Seconds in a month.
+    const fn month_seconds(self) -> i64 {
+        match self {
+            Self::Approximate => 30 * DAY_SECONDS,
+            Self::Systemd => 2_630_016,
+        }
+    }
+
+    /// Seconds in a year.
+    const fn year_seconds(self) -> i64 {
+        match self {
+            Self::Approximate => 365 * DAY_SECONDS,
+            Self::Systemd => 31_557_600,
+        }
+    }
+}
+
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+/// `a + b`, or as far as a [``Duration``] goes, so that adding up periods (which can be as long as
+/// any time span) never overflows.
+pub(crate) fn saturating_add(a: Duration, b: Duration) -> Duration {
+    a.checked_add(&b).unwrap_or_else(|| {
+        if b < Duration::zero() {
+            Duration::MIN
+        } else {
+            Duration::MAX
+        }
+    })
+}
+
+/// `a - b`, or as far as a [``Duration``] goes. See [``saturating_add``].
+pub(crate) fn saturating_sub(a: Duration, b: Duration) -> Duration {
+    a.checked_sub(&b).unwrap_or_else(|| {
+        if b < Duration::zero() {
+            Duration::MAX
+        } else {
+            Duration::MIN
+        }
+    })
+}
+
+/// Why a string isn't a time span. Displays with the offending part of the input pointed out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum TimeSpanError {
+    /// Nothing (or only whitespace) was given.
+    Empty,
+    /// A quantity was followed by something that isn't a unit, like the `x` of `2x`.
+    UnknownUnit {
+        input: String,
+        /// Byte offset of the unit.
+        offset: usize,
+        unit: String,
+    },
+    /// A quantity had no unit, like `2` or `2 3h`.
+    MissingUnit {
+        input: String,
+        /// Byte offset just past the quantity.
+        offset: usize,
+    },
+    /// Input which isn't part of a time span, from byte offset on.
+    Trailing { input: String, offset: usize },
+    /// Minutes or seconds of a clock-style span that aren't under 60, like the `75` of `1:75`.
+    OutOfRange {
+        input: String,
+        offset: usize,
+        field: String,
+    },
+    /// The span is longer than a duration can be.
+    Overflow,
+}
+
+impl TimeSpanError {
+    /// The same error, for a span found at byte `start` of the larger `input`.
+    pub(crate) fn within(self, input: &str, start: usize) -> Self {
+        let input = input.to_owned();
+        match self {
+            Self::Empty | Self::Overflow => self,
+            Self::UnknownUnit { offset, unit, .. } => Self::UnknownUnit {
+                input,
+                offset: start + offset,
+                unit,
+            },
+            Self::MissingUnit { offset, .. } => Self::MissingUnit {
+                input,
+                offset: start + offset,
+            },
+            Self::Trailing { offset, .. } => Self::Trailing {
+                input,
+                offset: start + offset,
+            },
+            Self::OutOfRange { offset, field, .. } => Self::OutOfRange {
+                input,
+                offset: start + offset,
+                field,
+            },
+        }
+    }
+
+    /// The input, where in it to point, and how many characters to point at.
+    fn location(&self) -> Option<(&str, usize, usize)> {
+        match self {
+            Self::Empty | Self::Overflow => None,
+            Self::UnknownUnit {
+                input,
+                offset,
+                unit,
+            } => Some((input, *offset, unit.chars().count())),
+            Self::MissingUnit { input, offset } => Some((input, *offset, 1)),
+            Self::Trailing { input, offset } => {
+                Some((input, *offset, input[*offset..].chars().count()))
+            }
+            Self::OutOfRange {
+                input,
+                offset,
+                field,
+            } => Some((input, *offset, field.chars().count())),
+        }
+    }
+}
+
+impl Display for TimeSpanError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no time span given")?,
+            Self::UnknownUnit { unit, .. } => write!(f, "unknown unit \"{unit}\"")?,
+            Self::MissingUnit { .. } => write!(f, "missing a unit, like h or min")?,
+            Self::Trailing { input, offset } => {
+                write!(f, "not sure what to do with \"{}\"", &input[*offset..])?;
+            }
+            Self::OutOfRange { field, .. } => write!(f, "\"{field}\" should be under 60")?,
+            Self::Overflow => write!(f, "time span is too long")?,
+        }
+        if let Some((input, offset, len)) = self.location() {
+            point_at(f, input, offset, len)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TimeSpanError {}
+
+/// Show `input` below an error, with `len` characters of it from byte `offset` pointed out.
+fn point_at(f: &mut Formatter, input: &str, offset: usize, len: usize) -> std::fmt::Result {
+    let indent = input[..offset].chars().count();
+    write!(
+        f,
+        "\n\n    {input}\n    {}{}",
+        " ".repeat(indent),
+        "^".repeat(len.max(1))
+    )
+}
+
+/// Convert a string to a duration in the default [``Calendar``]. See [``parse_time_span_in``].
+pub fn parse_time_span(string: &str) -> Result<Duration, TimeSpanError> {
+    parse_time_span_in(string, Calendar::default())
+}
+
 /// Convert a string to a duration. This wraps the pest parser and does some extra validation.
-pub fn parse_time_span(string: &str) -> Result<Duration> {
-    let time_span = SystemDStyleTimeParser::parse(Rule::time_span, string)?
+pub fn parse_time_span_in(string: &str, calendar: Calendar) -> Result<Duration, TimeSpanError> {
+    if string.trim().is_empty() {
+        return Err(TimeSpanError::Empty);
+    }
+    let time_span = SystemDStyleTimeParser::parse(Rule::time_span, string)
+        .map_err(|error| {
+            let end = prefix_end(string);
+            match error.location {
+                // as far as an ISO 8601 duration got, rather than a quantity-less "P..."
+                InputLocation::Pos(offset)
+                    if end == 0 && string.trim_start().starts_with(['P', 'p']) =>
+                {
+                    TimeSpanError::Trailing {
+                        input: string.to_owned(),
+                        offset,
+                    }
+                }
+                // a time span followed by another, like a preset and some units
+                _ if end > 0 && prefix_end(&string[end..]) > 0 => {
+                    let rest = &string[end..];
+                    TimeSpanError::Trailing {
+                        input: string.to_owned(),
+                        offset: string.len() - rest.trim_start().len(),
+                    }
+                }
+                _ => unparsed(string, end),
+            }
+        })?
         .next()
-        .unwrap();
-
-    let end = time_span.as_span().end_pos().pos();
-    if end != string.len() {
-        let matched = &string[..end];
-        let unmatched = &string[end..];
-        eprintln!(
-            "Invalid time span: \"{string}\"\n\n\
-                I was able to parse \"{matched}\" \
-                but I'm not sure what to do with the rest.\n\
-                Let me try \"{unmatched}\" on it's own and see if I can give you a more helpful error message.\n"
-        );
-        parse_time_span(unmatched)?;
-        bail!("Parse failed.");
+        .expect("a time span");
+
+    let mut nanoseconds: i128 = 0;
+    for element in time_span.into_inner() {
+        let element_nanoseconds = match element.as_rule() {
+            Rule::clock => clock_nanoseconds(string, element)?,
+            Rule::iso_duration => iso_nanoseconds(element, calendar)?,
+            Rule::preset => preset_nanoseconds(&element, calendar),
+            Rule::span_element => {
+                let mut element_pairs = element.into_inner();
+                let quantity = element_pairs.next().expect("a quantity");
+                let unit = element_pairs.next().expect("a unit");
+                scale(
+                    quantity.as_str(),
+                    unit_nanoseconds(string, &unit, calendar)?,
+                )?
+            }
+            Rule::EOI => continue,
+            _ => return Err(unparsed(string, element.as_span().start())),
+        };
+        nanoseconds = nanoseconds
+            .checked_add(element_nanoseconds)
+            .ok_or(TimeSpanError::Overflow)?;
     }
 
-    let span_elements = time_span.into_inner();
-
-    let mut duration = Duration::zero();
-
-    for element in span_elements {
-        let mut element_pairs = element.into_inner();
-        let quantity_pair = element_pairs.next().unwrap();
-        assert_eq!(quantity_pair.as_rule(), Rule::quantity);
-
-        let quantity = quantity_pair.as_str().trim().parse::<i64>()?;
-        let unit = element_pairs.next().unwrap().into_inner().next().unwrap();
-
-        let element_duration = match unit.as_rule() {
-            Rule::nanosecond => Duration::nanoseconds(quantity),
-            Rule::microsecond => Duration::microseconds(quantity),
-            Rule::millisecond => Duration::milliseconds(quantity),
-            Rule::second => Duration::seconds(quantity),
-            Rule::minute => Duration::minutes(quantity),
-            Rule::hour => Duration::hours(quantity),
-            Rule::day => Duration::days(quantity),
-            Rule::week => Duration::weeks(quantity),
-            // TODO: the following both round down, which I am okay with because input is integer
-            // anyways, so users would just use smalller units, but eventually something worth
-            // fixing to comply with systemd.time expectations.
-            Rule::month => Duration::days(quantity * 30),
-            Rule::year => Duration::days(quantity * 365),
-            Rule::time_span => todo!(),
-            Rule::span_element => todo!(),
-            Rule::quantity => todo!(),
-            Rule::unit => todo!(),
-            Rule::WHITESPACE => todo!(),
+    let seconds =
+        i64::try_from(nanoseconds / NANOS_PER_SECOND).map_err(|_| TimeSpanError::Overflow)?;
+    let nanos = u32::try_from(nanoseconds % NANOS_PER_SECOND).expect("under a second");
+    Duration::new(seconds, nanos).ok_or(TimeSpanError::Overflow)
+}
+
+/// How far into `input` a time span goes, or 0 if it doesn't start with one.
+fn prefix_end(input: &str) -> usize {
+    SystemDStyleTimeParser::parse(Rule::span_prefix, input)
+        .ok()
+        .and_then(|mut pairs| pairs.next())
+        .filter(|prefix| !prefix.as_str().trim().is_empty())
+        .map_or(0, |prefix| prefix.as_span().end())
+}
+
+/// Explain why `input` couldn't be parsed from `offset` on: a unit that isn't one, a missing unit,
+/// or something else entirely.
+fn unparsed(input: &str, offset: usize) -> TimeSpanError {
+    let rest = &input[offset..];
+    let offset = offset + (rest.len() - rest.trim_start().len());
+    let Some(quantity) = SystemDStyleTimeParser::parse(Rule::quantity, &input[offset..])
+        .ok()
+        .and_then(|mut pairs| pairs.next())
+    else {
+        return TimeSpanError::Trailing {
+            input: input.to_owned(),
+            offset,
         };
-        duration = duration + element_duration;
+    };
+    let after = &input[offset + quantity.as_str().len()..];
+    let unit_offset = input.len() - after.trim_start().len();
+    let unit: String = input[unit_offset..]
+        .chars()
+        .take_while(|c| !c.is_whitespace() && !c.is_ascii_digit())
+        .collect();
+    if unit.is_empty() {
+        TimeSpanError::MissingUnit {
+            input: input.to_owned(),
+            offset: offset + quantity.as_str().len(),
+        }
+    } else {
+        TimeSpanError::UnknownUnit {
+            input: input.to_owned(),
+            offset: unit_offset,
+            unit,
+        }
     }
-    Ok(duration)
+}
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const NANOS_PER_MINUTE: i128 = 60 * NANOS_PER_SECOND;
+const NANOS_PER_HOUR: i128 = 60 * NANOS_PER_MINUTE;
+
+/// Nanoseconds in one of the `unit` parsed from `input`.
+fn unit_nanoseconds(
+    input: &str,
+    unit: &Pair<Rule>,
+    calendar: Calendar,
+) -> Result<i128, TimeSpanError> {
+    let day = i128::from(DAY_SECONDS) * NANOS_PER_SECOND;
+    let rule = unit
+        .clone()
+        .into_inner()
+        .next()
+        .map_or(unit.as_rule(), |pair| pair.as_rule());
+    Ok(match rule {
+        Rule::nanosecond => 1,
+        Rule::microsecond => 1_000,
+        Rule::millisecond => 1_000_000,
+        Rule::second => NANOS_PER_SECOND,
+        Rule::minute => NANOS_PER_MINUTE,
+        Rule::hour => NANOS_PER_HOUR,
+        Rule::day => day,
+        Rule::week => 7 * day,
+        Rule::month => i128::from(calendar.month_seconds()) * NANOS_PER_SECOND,
+        Rule::year => i128::from(calendar.year_seconds()) * NANOS_PER_SECOND,
+        _ => {
+            return Err(TimeSpanError::UnknownUnit {
+                input: input.to_owned(),
+                offset: unit.as_span().start(),
+                unit: unit.as_str().to_owned(),
+            })
+        }
+    })
+}
+
+/// Nanoseconds in an ISO 8601 duration like `P1W3D` or `PT45M`.
+fn iso_nanoseconds(duration: Pair<Rule>, calendar: Calendar) -> Result<i128, TimeSpanError> {
+    let day = i128::from(DAY_SECONDS) * NANOS_PER_SECOND;
+    let mut nanoseconds: i128 = 0;
+    for part in duration.into_inner() {
+        let each = match part.as_rule() {
+            Rule::iso_years => i128::from(calendar.year_seconds()) * NANOS_PER_SECOND,
+            Rule::iso_months => i128::from(calendar.month_seconds()) * NANOS_PER_SECOND,
+            Rule::iso_weeks => 7 * day,
+            Rule::iso_days => day,
+            Rule::iso_hours => NANOS_PER_HOUR,
+            Rule::iso_minutes => NANOS_PER_MINUTE,
+            _ => NANOS_PER_SECOND,
+        };
+        let quantity = part.into_inner().next().expect("a quantity");
+        nanoseconds = nanoseconds
+            .checked_add(scale(quantity.as_str(), each)?)
+            .ok_or(TimeSpanError::Overflow)?;
+    }
+    Ok(nanoseconds)
+}
+
+/// Nanoseconds between practices at a named frequency like `weekly` or `twice a week`.
+fn preset_nanoseconds(preset: &Pair<Rule>, calendar: Calendar) -> i128 {
+    let day = i128::from(DAY_SECONDS) * NANOS_PER_SECOND;
+    let month = i128::from(calendar.month_seconds()) * NANOS_PER_SECOND;
+    let rule = preset
+        .clone()
+        .into_inner()
+        .next()
+        .map_or(preset.as_rule(), |pair| pair.as_rule());
+    match rule {
+        Rule::hourly => NANOS_PER_HOUR,
+        Rule::twice_daily => day / 2,
+        Rule::every_other_day => 2 * day,
+        Rule::twice_weekly => 7 * day / 2,
+        Rule::weekly => 7 * day,
+        Rule::fortnightly => 14 * day,
+        Rule::twice_monthly => month / 2,
+        Rule::monthly => month,
+        Rule::quarterly => 3 * month,
+        Rule::yearly => i128::from(calendar.year_seconds()) * NANOS_PER_SECOND,
+        _ => day,
+    }
+}
+
+/// A whole or decimal quantity (like `2`, `1.5` or `.5`) of something `each` nanoseconds long,
+/// rounded down to the nanosecond.
+fn scale(quantity: &str, each: i128) -> Result<i128, TimeSpanError> {
+    let (whole, fraction) = quantity.split_once('.').unwrap_or((quantity, ""));
+    // past this, digits are far finer than a nanosecond of even a year
+    let fraction = &fraction[..fraction.len().min(18)];
+    let digits = format!("{whole}{fraction}")
+        .parse::<i128>()
+        .map_err(|_| TimeSpanError::Overflow)?;
+    let denominator = 10_i128.pow(u32::try_from(fraction.len()).expect("at most 18"));
+    let scaled = digits.checked_mul(each).ok_or(TimeSpanError::Overflow)?;
+    Ok(scaled / denominator)
+}
+
+/// Nanoseconds in a clock-style span like `1:30` or `1:30:00`, parsed from `input`.
+fn clock_nanoseconds(input: &str, clock: Pair<Rule>) -> Result<i128, TimeSpanError> {
+    let mut nanoseconds: i128 = 0;
+    for field in clock.into_inner() {
+        let value = field.as_str();
+        let (each, limited) = match field.as_rule() {
+            Rule::clock_hours => (NANOS_PER_HOUR, false),
+            Rule::clock_minutes => (NANOS_PER_MINUTE, true),
+            _ => (NANOS_PER_SECOND, true),
+        };
+        if limited && !value.starts_with(['0', '1', '2', '3', '4', '5']) {
+            return Err(TimeSpanError::OutOfRange {
+                input: input.to_owned(),
+                offset: field.as_span().start(),
+                field: value.to_owned(),
+            });
+        }
+        nanoseconds = nanoseconds
+            .checked_add(scale(value, each)?)
+            .ok_or(TimeSpanError::Overflow)?;
+    }
+    Ok(nanoseconds)
 }
 
 /// For unrolling duration into a human readable display
@@ -84,13 +435,20 @@ pub struct FlatTime {
     ns: i64,
 }
 
+impl FlatTime {
+    /// Each unit's value, from years down to nanoseconds, alongside the unit's abbreviation.
+    pub fn iter(&self) -> <&Self as IntoIterator>::IntoIter {
+        self.into_iter()
+    }
+}
+
 impl IntoIterator for &FlatTime {
     type Item = (i64, &'static str);
     type IntoIter = Box<dyn Iterator<Item = Self::Item>>;
 
     fn into_iter(
         self,
-    ) -> std::boxed::Box<(dyn std::iter::Iterator<Item = (i64, &'static str)> + 'static)> {
+    ) -> std::boxed::Box<dyn std::iter::Iterator<Item = (i64, &'static str)> + 'static> {
         let values = [
             self.y, self.M, self.w, self.d, self.h, self.m, self.s, self.ms, self.us, self.ns,
         ];
@@ -122,7 +480,7 @@ impl FlatTime {
                 result.push(' ');
             }
             first = false;
-            result.push_str(&format!("{value}{unit}"));
+            let _ = write!(result, "{value}{unit}");
         }
         if result.is_empty() {
             result.push_str("0s");
@@ -142,39 +500,45 @@ impl FlatTime {
                 result.push(' ');
             }
             first = false;
-            result.push_str(&format!("{value}{unit}"));
+            let _ = write!(result, "{value}{unit}");
         }
         if result.is_empty() {
             result.push_str("0s");
         }
         result
     }
+}
 
+impl From<Duration> for FlatTime {
+    /// Unroll in the default [``Calendar``].
+    fn from(value: Duration) -> Self {
+        Self::in_calendar(value, Calendar::default())
+    }
 }
 
 #[allow(non_snake_case, clippy::many_single_char_names)]
-impl From<Duration> for FlatTime {
-    fn from(mut value: Duration) -> Self {
-        let mut days = value.num_days();
-        let y = days / 365;
-        days %= 365;
-        let M = days / 30;
-        value = value - Duration::days(y * 365 + M * 30);
-        let w = value.num_weeks();
-        value = value - Duration::weeks(w);
-        let d = value.num_days();
-        value = value - Duration::days(d);
-        let h = value.num_hours();
-        value = value - Duration::hours(h);
-        let m = value.num_minutes();
-        value = value - Duration::minutes(m);
-        let s = value.num_seconds();
-        value = value - Duration::seconds(s);
-        let ms = value.num_milliseconds();
-        value = value - Duration::milliseconds(ms);
-        let us = value.num_microseconds().expect("value too extreme");
-        value = value - Duration::microseconds(us);
-        let ns = value.num_nanoseconds().expect("value too extreme");
+impl FlatTime {
+    /// Unroll with months and years as long as in `calendar`, so that parsing the
+    /// [``FlatTime::format``] in the same calendar gives back `value`.
+    pub fn in_calendar(value: Duration, calendar: Calendar) -> Self {
+        // plain integer division, so nothing overflows however long the duration
+        let mut seconds = value.num_seconds();
+        let y = seconds / calendar.year_seconds();
+        seconds %= calendar.year_seconds();
+        let M = seconds / calendar.month_seconds();
+        seconds %= calendar.month_seconds();
+        let w = seconds / (7 * DAY_SECONDS);
+        seconds %= 7 * DAY_SECONDS;
+        let d = seconds / DAY_SECONDS;
+        seconds %= DAY_SECONDS;
+        let h = seconds / (60 * 60);
+        seconds %= 60 * 60;
+        let m = seconds / 60;
+        let s = seconds % 60;
+        let nanos = i64::from(value.subsec_nanos());
+        let ms = nanos / 1_000_000;
+        let us = nanos / 1_000 % 1_000;
+        let ns = nanos % 1_000;
         Self {
             y,
             M,
@@ -206,9 +570,13 @@ const _UNITS_LONG: [&str; 10] = [
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_time_span, Rule, SystemDStyleTimeParser};
+    use super::{
+        parse_time_span, parse_time_span_in, Calendar, FlatTime, Rule, SystemDStyleTimeParser,
+        TimeSpanError,
+    };
     use chrono::Duration;
     use pest::Parser;
+    use proptest::prelude::*;
 
     // Testing valid time span expressions
     #[test]
@@ -252,7 +620,52 @@ mod tests {
 
     #[test]
     fn test_parse_empty() {
-        assert!(parse_time_span("").is_err());
+        assert_eq!(parse_time_span(""), Err(TimeSpanError::Empty));
+        assert_eq!(parse_time_span("  "), Err(TimeSpanError::Empty));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let error = parse_time_span("2h 5 parsecs").unwrap_err();
+        assert_eq!(
+            error,
+            TimeSpanError::UnknownUnit {
+                input: "2h 5 parsecs".to_owned(),
+                offset: 5,
+                unit: "parsecs".to_owned(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "unknown unit \"parsecs\"\n\n    2h 5 parsecs\n         ^^^^^^^"
+        );
+
+        assert_eq!(
+            parse_time_span("2h 5"),
+            Err(TimeSpanError::MissingUnit {
+                input: "2h 5".to_owned(),
+                offset: 4,
+            })
+        );
+        assert_eq!(
+            parse_time_span("1h watermelon"),
+            Err(TimeSpanError::Trailing {
+                input: "1h watermelon".to_owned(),
+                offset: 3,
+            })
+        );
+        assert_eq!(
+            parse_time_span("1:75"),
+            Err(TimeSpanError::OutOfRange {
+                input: "1:75".to_owned(),
+                offset: 2,
+                field: "75".to_owned(),
+            })
+        );
+        assert_eq!(
+            parse_time_span("999999999999y"),
+            Err(TimeSpanError::Overflow)
+        );
     }
 
     #[test]
@@ -271,6 +684,174 @@ mod tests {
         assert!(hard.is_ok_and(|d| d == hard_answer));
     }
 
+    #[test]
+    fn test_parse_decimals_and_clocks() {
+        let cases = [
+            ("1.5h", Duration::minutes(90)),
+            (".5d", Duration::hours(12)),
+            ("2.w", Duration::weeks(2)),
+            ("1:30", Duration::minutes(90)),
+            ("1:30:00", Duration::minutes(90)),
+            ("0:00:12.5", Duration::milliseconds(12_500)),
+            ("1d 2:30", Duration::days(1) + Duration::minutes(150)),
+            ("0.1s", Duration::milliseconds(100)),
+        ];
+        for (time_span, answer) in cases {
+            assert_eq!(parse_time_span(time_span).unwrap(), answer, "{time_span}");
+        }
+
+        for time_span in ["1:60", "1:5", "1:00:60", "1.5.5h", "1:30h"] {
+            assert!(parse_time_span(time_span).is_err(), "{time_span}");
+        }
+    }
+
+    #[test]
+    fn test_parse_iso_durations_and_presets() {
+        let cases = [
+            ("P1W3D", Duration::days(10)),
+            ("PT45M", Duration::minutes(45)),
+            ("p1dt1.5h", Duration::days(1) + Duration::minutes(90)),
+            ("P1Y", Duration::days(365)),
+            ("daily", Duration::days(1)),
+            ("every other day", Duration::days(2)),
+            ("Twice a week", Duration::hours(84)),
+            ("weekly", Duration::weeks(1)),
+            ("fortnightly", Duration::weeks(2)),
+            ("every two weeks", Duration::weeks(2)),
+            ("monthly", Duration::days(30)),
+            ("quarterly", Duration::days(90)),
+            ("annually", Duration::days(365)),
+        ];
+        for (time_span, answer) in cases {
+            assert_eq!(parse_time_span(time_span).unwrap(), answer, "{time_span}");
+        }
+
+        // M is still month before minute, and in ISO 8601 month before the T and minute after
+        let month = Duration::days(30);
+        for (time_span, answer) in [
+            ("1M", month),
+            ("1m", Duration::minutes(1)),
+            ("1month", month),
+            ("1min", Duration::minutes(1)),
+            ("P1M", month),
+            ("PT1M", Duration::minutes(1)),
+            ("P1MT1M", month + Duration::minutes(1)),
+        ] {
+            assert_eq!(parse_time_span(time_span).unwrap(), answer, "{time_span}");
+        }
+
+        for time_span in ["P", "PT", "P1H", "P1D1W2", "twice", "every other"] {
+            assert!(parse_time_span(time_span).is_err(), "{time_span}");
+        }
+
+        // presets and ISO 8601 durations stand alone, with each ISO part once and in order
+        for (time_span, offset) in [
+            ("weekly daily", 7),
+            ("P1D 3h", 4),
+            ("daily 1:30", 6),
+            ("1h weekly", 3),
+            ("P1D1Y1D", 3),
+            ("P1D1D", 3),
+            ("PT1S1H", 4),
+        ] {
+            assert_eq!(
+                parse_time_span(time_span),
+                Err(TimeSpanError::Trailing {
+                    input: time_span.to_owned(),
+                    offset
+                }),
+                "{time_span}"
+            );
+        }
+    }
+
+    #[test]
+    fn calendars_round_trip() {
+        let systemd = Calendar::Systemd;
+        assert_eq!(
+            parse_time_span_in("1M", systemd).unwrap(),
+            Duration::seconds(2_630_016)
+        );
+        assert_eq!(
+            parse_time_span_in("1y", systemd).unwrap(),
+            Duration::hours(365 * 24 + 6)
+        );
+        assert_eq!(
+            parse_time_span_in("1M", Calendar::Approximate).unwrap(),
+            Duration::days(30)
+        );
+
+        let durations = [
+            Duration::days(365),
+            Duration::days(400) + Duration::milliseconds(1),
+            Duration::days(3 * 365 + 100) + Duration::hours(7) + Duration::nanoseconds(3),
+            Duration::seconds(2_630_016 * 13),
+        ];
+        for calendar in [Calendar::Approximate, systemd] {
+            for duration in durations {
+                let formatted = FlatTime::in_calendar(duration, calendar).format();
+                assert_eq!(
+                    parse_time_span_in(&formatted, calendar).unwrap(),
+                    duration,
+                    "{formatted} in {calendar:?}"
+                );
+            }
+        }
+        assert_eq!(
+            FlatTime::in_calendar(Duration::days(365), systemd).format(),
+            "11M 4w 2d 3h 50m 24s"
+        );
+    }
+
+    #[test]
+    fn extremes_dont_overflow() {
+        for calendar in [Calendar::Approximate, Calendar::Systemd] {
+            for duration in [Duration::MAX, Duration::MIN, Duration::zero()] {
+                FlatTime::in_calendar(duration, calendar).format();
+            }
+            let formatted = FlatTime::in_calendar(Duration::MAX, calendar).format();
+            assert_eq!(parse_time_span_in(&formatted, calendar), Ok(Duration::MAX));
+        }
+        assert_eq!(
+            parse_time_span("999999999999999y"),
+            Err(TimeSpanError::Overflow)
+        );
+        assert_eq!(
+            parse_time_span("99999999999999999999999999999999999999999s"),
+            Err(TimeSpanError::Overflow)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_never_panics(string in "\\PC*") {
+            let _ = parse_time_span_in(&string, Calendar::Systemd);
+        }
+
+        #[test]
+        fn grammatical_spans_parse_or_overflow(
+            string in "(([0-9]{1,20}(\\.[0-9]{0,20})?|\\.[0-9]{1,20}) ?(y|M|w|d|h|min|m|s|ms|us|ns) ?\
+                |[0-9]{1,20}:[0-5][0-9](:[0-5][0-9](\\.[0-9]{1,20})?)? ){1,6}",
+        ) {
+            match parse_time_span(&string) {
+                Ok(_) | Err(TimeSpanError::Overflow) => {}
+                Err(error) => prop_assert!(false, "{error}"),
+            }
+        }
+
+        #[test]
+        fn flattening_round_trips(
+            seconds in 0..Duration::MAX.num_seconds(),
+            nanos in 0..1_000_000_000_u32,
+            systemd: bool,
+        ) {
+            let duration = Duration::new(seconds, nanos).unwrap();
+            let calendar = if systemd { Calendar::Systemd } else { Calendar::Approximate };
+            let formatted = FlatTime::in_calendar(duration, calendar).format();
+            prop_assert_eq!(parse_time_span_in(&formatted, calendar), Ok(duration));
+        }
+    }
+
     #[test]
     fn test_display() {
         use crate::time::FlatTime;
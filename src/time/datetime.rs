@@ -0,0 +1,347 @@
+//! Instants like `yesterday 18:00`, `last monday`, `3h ago` or `2024-05-01 19:30`, as the
+//! companion of [``parse_time_span``](super::parse_time_span).
+
+use super::{parse_time_span_in, point_at, Calendar, TimeSpanError};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use pest::error::InputLocation;
+use pest::iterators::Pair;
+use pest::Parser;
+use std::fmt::{Display, Formatter};
+
+#[derive(pest_derive::Parser)]
+#[grammar = "time/datetime.pest"]
+struct DateTimeParser;
+
+/// Why a string isn't a date and time. Displays with the offending part of the input pointed out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+pub enum DateTimeError {
+    /// Nothing (or only whitespace) was given.
+    Empty,
+    /// Input which isn't part of a date or time, from byte offset on.
+    Unexpected { input: String, offset: usize },
+    /// A date that isn't on the calendar, like `2024-02-30`.
+    InvalidDate {
+        input: String,
+        offset: usize,
+        date: String,
+    },
+    /// A time that isn't on the clock, like `25:00` or `13pm`.
+    InvalidTime {
+        input: String,
+        offset: usize,
+        time: String,
+    },
+    /// A local time which never happened, as when clocks go forward.
+    Skipped { input: String },
+    /// The time span of an `ago`.
+    Span(TimeSpanError),
+}
+
+impl Display for DateTimeError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no date or time given"),
+            Self::Unexpected { input, offset } => {
+                let rest = &input[*offset..];
+                if rest.is_empty() {
+                    write!(f, "ended too soon")?;
+                } else {
+                    write!(f, "not sure what to do with \"{rest}\"")?;
+                }
+                point_at(f, input, *offset, rest.chars().count())
+            }
+            Self::InvalidDate {
+                input,
+                offset,
+                date,
+            } => {
+                write!(f, "there's no {date}")?;
+                point_at(f, input, *offset, date.chars().count())
+            }
+            Self::InvalidTime {
+                input,
+                offset,
+                time,
+            } => {
+                write!(f, "there's no {time} in a day")?;
+                point_at(f, input, *offset, time.chars().count())
+            }
+            Self::Skipped { input } => {
+                write!(f, "clocks skipped over {input} here")?;
+                point_at(f, input, 0, input.chars().count())
+            }
+            Self::Span(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DateTimeError {}
+
+/// Resolve a date and time, as of `now`, in the local time zone. See [``parse_datetime_in``].
+pub fn parse_datetime(
+    input: &str,
+    now: DateTime<Utc>,
+    calendar: Calendar,
+) -> Result<DateTime<Utc>, DateTimeError> {
+    parse_datetime_in(input, now, &Local, calendar)
+}
+
+/// Resolve a date and time, as of `now`, in `zone`. Any of:
+///
+/// - RFC 3339, like `2024-05-01T19:30:00+02:00`
+/// - a date, a day like `yesterday`, or a weekday like `monday` (the latest, which may be today)
+///   or `last monday` (the one before today), optionally followed by a time of day
+/// - a time of day, like `18:00`, `6:30pm` or `6pm`, which is today
+/// - a time span and `ago`, like `3h ago`, with months and years as long as in `calendar`
+///
+/// Without a time of day, `today`, `yesterday` and `tomorrow` keep the current time of day, and
+/// other days begin at midnight.
+pub fn parse_datetime_in<Tz: TimeZone>(
+    input: &str,
+    now: DateTime<Utc>,
+    zone: &Tz,
+    calendar: Calendar,
+) -> Result<DateTime<Utc>, DateTimeError> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err(DateTimeError::Empty);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    if let Some(span) = strip_ago(input) {
+        // the span is the start of the input, so errors point into the whole of it
+        let span = parse_time_span_in(span, calendar)
+            .map_err(|error| DateTimeError::Span(error.within(input, 0)))?;
+        return now
+            .checked_sub_signed(span)
+            .ok_or(DateTimeError::Span(TimeSpanError::Overflow));
+    }
+
+    let datetime = DateTimeParser::parse(Rule::datetime, input)
+        .map_err(|error| {
+            let offset = match error.location {
+                InputLocation::Pos(offset) | InputLocation::Span((offset, _)) => offset,
+            };
+            DateTimeError::Unexpected {
+                input: input.to_owned(),
+                offset,
+            }
+        })?
+        .next()
+        .expect("a datetime");
+
+    let local_now = now.with_timezone(zone);
+    let today = local_now.date_naive();
+    let mut date = today;
+    let mut time = None;
+    for pair in datetime.into_inner() {
+        match pair.as_rule() {
+            Rule::date => date = date_of(input, &pair)?,
+            Rule::relative_day => {
+                date = match inner_rule(&pair) {
+                    Rule::yesterday => today - Days::new(1),
+                    Rule::tomorrow => today + Days::new(1),
+                    _ => today,
+                };
+                time = Some(local_now.time());
+            }
+            Rule::weekday => date = latest(today, weekday_of(&pair), false),
+            Rule::last_weekday => {
+                let weekday = pair.into_inner().next().expect("a weekday");
+                date = latest(today, weekday_of(&weekday), true);
+            }
+            Rule::time_of_day => time = Some(time_of(input, &pair)?),
+            _ => {}
+        }
+    }
+
+    let local = date.and_time(time.unwrap_or(NaiveTime::MIN));
+    zone.from_local_datetime(&local)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .ok_or_else(|| DateTimeError::Skipped {
+            input: input.to_owned(),
+        })
+}
+
+/// The time span before a trailing `ago`, if there is one.
+fn strip_ago(input: &str) -> Option<&str> {
+    let trimmed = input.trim_end();
+    let span = trimmed.len().checked_sub("ago".len())?;
+    let (span, ago) = (trimmed.get(..span)?, &trimmed[span..]);
+    (ago.eq_ignore_ascii_case("ago") && span.ends_with(char::is_whitespace)).then_some(span)
+}
+
+/// The only rule inside `pair`.
+fn inner_rule(pair: &Pair<Rule>) -> Rule {
+    pair.clone()
+        .into_inner()
+        .next()
+        .map_or(pair.as_rule(), |inner| inner.as_rule())
+}
+
+/// The latest `weekday` up to `today`, or before it if `before`.
+fn latest(today: NaiveDate, weekday: Weekday, before: bool) -> NaiveDate {
+    let back = (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    let back = if before && back == 0 { 7 } else { back };
+    today - Days::new(back.into())
+}
+
+fn weekday_of(pair: &Pair<Rule>) -> Weekday {
+    match inner_rule(pair) {
+        Rule::monday => Weekday::Mon,
+        Rule::tuesday => Weekday::Tue,
+        Rule::wednesday => Weekday::Wed,
+        Rule::thursday => Weekday::Thu,
+        Rule::friday => Weekday::Fri,
+        Rule::saturday => Weekday::Sat,
+        _ => Weekday::Sun,
+    }
+}
+
+fn date_of(input: &str, date: &Pair<Rule>) -> Result<NaiveDate, DateTimeError> {
+    let mut fields = date.clone().into_inner().map(|field| field.as_str());
+    let mut next = || fields.next().and_then(|field| field.parse::<u32>().ok());
+    let (year, month, day) = (next(), next(), next());
+    year.and_then(|year| i32::try_from(year).ok())
+        .zip(month.zip(day))
+        .and_then(|(year, (month, day))| NaiveDate::from_ymd_opt(year, month, day))
+        .ok_or_else(|| DateTimeError::InvalidDate {
+            input: input.to_owned(),
+            offset: date.as_span().start(),
+            date: date.as_str().to_owned(),
+        })
+}
+
+fn time_of(input: &str, time: &Pair<Rule>) -> Result<NaiveTime, DateTimeError> {
+    let invalid = || DateTimeError::InvalidTime {
+        input: input.to_owned(),
+        offset: time.as_span().start(),
+        time: time.as_str().trim_end().to_owned(),
+    };
+    let (mut hour, mut minute, mut second, mut half) = (0, 0, 0, None);
+    for field in time.clone().into_inner() {
+        let value = || field.as_str().parse::<u32>().map_err(|_| invalid());
+        match field.as_rule() {
+            Rule::hour => hour = value()?,
+            Rule::minute => minute = value()?,
+            Rule::second => second = value()?,
+            _ => half = Some(inner_rule(&field)),
+        }
+    }
+    if let Some(half) = half {
+        if !(1..=12).contains(&hour) {
+            return Err(invalid());
+        }
+        hour %= 12;
+        if half == Rule::pm {
+            hour += 12;
+        }
+    }
+    NaiveTime::from_hms_opt(hour, minute, second).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_datetime_in, DateTimeError};
+    use crate::time::{Calendar, TimeSpanError};
+    use chrono::{DateTime, Duration, FixedOffset, Utc};
+
+    fn utc(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().into()
+    }
+
+    #[test]
+    fn datetimes_resolve_in_the_zone() {
+        // a Wednesday, at 14:00 in the zone
+        let now = utc("2024-05-08T12:00:00Z");
+        let zone = FixedOffset::east_opt(2 * 60 * 60).unwrap();
+        let cases = [
+            ("2024-05-01T19:30:00+02:00", utc("2024-05-01T17:30:00Z")),
+            ("2024-05-01", utc("2024-04-30T22:00:00Z")),
+            ("2024-05-01 19:30", utc("2024-05-01T17:30:00Z")),
+            ("2024-05-01T19:30", utc("2024-05-01T17:30:00Z")),
+            ("18:00", utc("2024-05-08T16:00:00Z")),
+            ("6pm", utc("2024-05-08T16:00:00Z")),
+            ("6:30 PM", utc("2024-05-08T16:30:00Z")),
+            ("12am", utc("2024-05-07T22:00:00Z")),
+            ("yesterday 18:00", utc("2024-05-07T16:00:00Z")),
+            ("yesterday", now - Duration::days(1)),
+            ("monday", utc("2024-05-05T22:00:00Z")),
+            ("wed", utc("2024-05-07T22:00:00Z")),
+            ("last wednesday", utc("2024-04-30T22:00:00Z")),
+            ("Last Monday 9am", utc("2024-05-06T07:00:00Z")),
+            ("3h ago", now - Duration::hours(3)),
+            ("1d 2:30 ago", now - Duration::minutes(60 * 24 + 150)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                parse_datetime_in(input, now, &zone, Calendar::default()),
+                Ok(expected),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let now = utc("2024-05-08T12:00:00Z");
+        let parse = |input| parse_datetime_in(input, now, &Utc, Calendar::default());
+
+        assert_eq!(parse(" "), Err(DateTimeError::Empty));
+        assert_eq!(
+            parse("2024-02-30 18:00"),
+            Err(DateTimeError::InvalidDate {
+                input: "2024-02-30 18:00".to_owned(),
+                offset: 0,
+                date: "2024-02-30".to_owned(),
+            })
+        );
+        assert!(matches!(
+            parse("25:00"),
+            Err(DateTimeError::InvalidTime { offset: 0, .. })
+        ));
+        assert!(matches!(
+            parse("yesterday 13pm"),
+            Err(DateTimeError::InvalidTime { offset: 10, .. })
+        ));
+        let error = parse("3 parsecs ago").unwrap_err();
+        assert!(matches!(
+            error,
+            DateTimeError::Span(TimeSpanError::UnknownUnit { offset: 2, .. })
+        ));
+        assert_eq!(
+            error.to_string(),
+            "unknown unit \"parsecs\"\n\n    3 parsecs ago\n      ^^^^^^^"
+        );
+        for input in [
+            "todayt",
+            "todayt18:00",
+            "yesterdays",
+            "mondays",
+            "lastmonday",
+            "fri9am",
+        ] {
+            assert!(
+                matches!(parse(input), Err(DateTimeError::Unexpected { .. })),
+                "{input}"
+            );
+        }
+
+        let error = parse("yesterday at 6").unwrap_err();
+        assert_eq!(
+            error,
+            DateTimeError::Unexpected {
+                input: "yesterday at 6".to_owned(),
+                offset: 10,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "not sure what to do with \"at 6\"\n\n    yesterday at 6\n              ^^^^"
+        );
+    }
+}
@@ -1,11 +1,85 @@
-use anyhow::{Context, Result};
-
-/// Write content to file
-pub fn long_edit(intitial_content: Option<&str>) -> Result<String> {
-    dialoguer::Editor::new()
-        .require_save(false)
-        .edit(intitial_content.unwrap_or_default())?
-        .context("Content not saved")
+/// Width to draw at when there's no terminal to measure and no `$COLUMNS`.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Glyphs bars are drawn with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BarStyle {
+    /// `▬`, which not every terminal font has.
+    #[default]
+    Unicode,
+    /// `#`, for everywhere else.
+    Ascii,
+}
+
+/// Width to draw at: the terminal's if there is one, otherwise `$COLUMNS`, otherwise
+/// [``DEFAULT_WIDTH``].
+pub fn terminal_width() -> usize {
+    let terminal = termion::terminal_size().ok().map(|(width, _)| width);
+    width_from(terminal, std::env::var("COLUMNS").ok().as_deref())
+}
+
+fn width_from(terminal: Option<u16>, columns: Option<&str>) -> usize {
+    terminal
+        .filter(|&width| width > 0)
+        .map(usize::from)
+        .or_else(|| columns?.trim().parse().ok().filter(|&width| width > 0))
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+impl BarStyle {
+    const fn glyph(self) -> &'static str {
+        match self {
+            Self::Unicode => "\u{025AC}",
+            Self::Ascii => "#",
+        }
+    }
+
+    /// Glyph for the part of a bar past the end of its period.
+    const fn overflow_glyph(self) -> &'static str {
+        match self {
+            Self::Unicode => "\u{025AD}",
+            Self::Ascii => "+",
+        }
+    }
+
+    /// Marks the end of the period on a bar which hasn't reached it.
+    const fn period_mark(self) -> char {
+        match self {
+            Self::Unicode => '\u{2502}',
+            Self::Ascii => '|',
+        }
+    }
+}
+
+/// A bar drawn on a scale of `scale` periods (at least one), so that it can show how far past its
+/// period it is. Returned as the filled part and the rest, which together are `bar_width` wide.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn overflow_bar(
+    bar_width: usize,
+    fraction: f64,
+    scale: f64,
+    style: BarStyle,
+) -> (String, String) {
+    let scale = scale.max(1.0);
+    let cells = |fraction: f64| ((fraction / scale).clamp(0.0, 1.0) * bar_width as f64) as usize;
+    let filled = cells(fraction);
+    let period_end = cells(1.0);
+
+    let within = filled.min(period_end);
+    let filled_part = format!(
+        "{}{}",
+        style.glyph().repeat(within),
+        style.overflow_glyph().repeat(filled - within)
+    );
+    let mut rest = vec![' '; bar_width - filled];
+    if period_end < bar_width && filled < period_end {
+        rest[period_end - filled] = style.period_mark();
+    }
+    (filled_part, rest.into_iter().collect())
 }
 
 /// generate a bar for a practice
@@ -14,11 +88,43 @@ pub fn long_edit(intitial_content: Option<&str>) -> Result<String> {
     clippy::cast_possible_truncation,
     clippy::cast_sign_loss
 )]
-pub fn bar(bar_width: usize, mut fraction: f64) -> String {
+pub fn bar(bar_width: usize, mut fraction: f64, style: BarStyle) -> String {
     fraction = fraction.clamp(0.0, 1.0);
     //.with_context(|| "fraction must be between 0 and 1")?;
     let filled = (fraction * bar_width as f64) as usize;
     let empty = bar_width - filled;
     assert!(filled + empty == bar_width);
-    format!("{}{}", "\u{025AC}".repeat(filled), " ".repeat(empty))
+    format!("{}{}", style.glyph().repeat(filled), " ".repeat(empty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bar, overflow_bar, width_from, BarStyle, DEFAULT_WIDTH};
+
+    #[test]
+    fn width_falls_back_to_columns_then_default() {
+        assert_eq!(width_from(Some(120), Some("100")), 120);
+        assert_eq!(width_from(None, Some("100")), 100);
+        assert_eq!(width_from(Some(0), Some(" 100 ")), 100);
+        assert_eq!(width_from(None, Some("wide")), DEFAULT_WIDTH);
+        assert_eq!(width_from(None, None), DEFAULT_WIDTH);
+    }
+
+    #[test]
+    fn ascii_bars() {
+        assert_eq!(bar(4, 0.5, BarStyle::Ascii), "##  ");
+        assert_eq!(bar(4, 2.0, BarStyle::Ascii), "####");
+    }
+
+    #[test]
+    fn overflow_bars_show_how_far_past() {
+        let bar = |fraction| overflow_bar(8, fraction, 2.0, BarStyle::Ascii);
+        assert_eq!(bar(0.5), ("##".to_owned(), "  |   ".to_owned()));
+        assert_eq!(bar(1.5), ("####++".to_owned(), "  ".to_owned()));
+        assert_eq!(bar(5.0), ("####++++".to_owned(), String::new()));
+        assert_eq!(
+            overflow_bar(4, 0.5, 1.0, BarStyle::Ascii),
+            ("##".to_owned(), "  ".to_owned())
+        );
+    }
 }